@@ -0,0 +1,492 @@
+//! An implementation of the [`CloudFileSigner`] trait for Amazon CloudFront.
+
+use std::fmt::{Debug, Formatter};
+use std::time::{Duration, SystemTime};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs1v15::SigningKey;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::signature::{SignatureEncoding, Signer};
+use rsa::RsaPrivateKey;
+use sha1::Sha1;
+
+use crate::encoding::{encode_key, query_separator};
+use crate::telemetry::instrument_sign;
+use crate::time::epoch_seconds;
+use crate::{CloudFileSigner, Permission, PresignedUrl, Provider, SignerError};
+
+use super::uri::S3Uri;
+
+/// A signer for content served through Amazon CloudFront.
+///
+/// CloudFront signed URLs and signed cookies are signed offline with the
+/// private key of a key pair registered with the distribution. Paths are
+/// resolved against the domain of the distribution:
+///
+/// - `https://` URLs are signed as is.
+/// - `s3://` URIs are served from the distribution using the object key as
+///   the path. The bucket must be the origin of the distribution.
+/// - Other paths are appended to the distribution domain.
+///
+/// # Example
+/// ```no_run
+/// # use cloud_file_signer::SignerError;
+/// # fn main() -> Result<(), SignerError> {
+/// use std::time::{Duration, SystemTime};
+/// use cloud_file_signer::aws::{CloudFrontPolicy, CloudFrontSigner};
+///
+/// let pem = std::fs::read_to_string("private_key.pem").unwrap();
+/// let signer = CloudFrontSigner::from_pem("K2JCJMDEHXQW5F", "https://d111111abcdef8.cloudfront.net", &pem)?;
+///
+/// let expires = SystemTime::now() + Duration::from_secs(3600);
+/// let url = signer.sign_canned_url("https://d111111abcdef8.cloudfront.net/image.jpg", expires)?;
+///
+/// let policy = CloudFrontPolicy::new("https://d111111abcdef8.cloudfront.net/images/*", expires)
+///     .with_ip_range("192.0.2.0/24");
+/// let cookies = signer.signed_cookies(&policy)?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone)]
+pub struct CloudFrontSigner {
+    key_pair_id: String,
+    domain: String,
+    signing_key: SigningKey<Sha1>,
+}
+
+impl CloudFrontSigner {
+    /// Create a new signer for the CloudFront distribution served from
+    /// `domain`, for example `https://d111111abcdef8.cloudfront.net`.
+    pub fn new(
+        key_pair_id: impl Into<String>,
+        domain: impl Into<String>,
+        private_key: RsaPrivateKey,
+    ) -> Self {
+        Self {
+            key_pair_id: key_pair_id.into(),
+            domain: domain.into().trim_end_matches('/').to_string(),
+            signing_key: SigningKey::new(private_key),
+        }
+    }
+
+    /// Create a new signer from a PEM encoded RSA private key, in either
+    /// PKCS#1 or PKCS#8 format.
+    pub fn from_pem(
+        key_pair_id: impl Into<String>,
+        domain: impl Into<String>,
+        private_key_pem: &str,
+    ) -> Result<Self, SignerError> {
+        let private_key = RsaPrivateKey::from_pkcs1_pem(private_key_pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs8_pem(private_key_pem))
+            .map_err(|e| {
+                SignerError::signing_error(format!(
+                    "Could not parse the CloudFront private key. Cause: {e}"
+                ))
+            })?;
+        Ok(Self::new(key_pair_id, domain, private_key))
+    }
+
+    /// Return the id of the key pair used for signing.
+    #[must_use]
+    pub fn key_pair_id(&self) -> &str {
+        &self.key_pair_id
+    }
+
+    /// Return the domain of the distribution.
+    #[must_use]
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Sign a URL with a canned policy. The URL is valid until `expires`.
+    pub fn sign_canned_url(&self, url: &str, expires: SystemTime) -> Result<String, SignerError> {
+        let expires = epoch_seconds(expires)?;
+        let policy = format!(
+            r#"{{"Statement":[{{"Resource":"{}","Condition":{{"DateLessThan":{{"AWS:EpochTime":{expires}}}}}}}]}}"#,
+            json_escape(url)
+        );
+        let signature = self.sign_policy(&policy);
+        Ok(format!(
+            "{url}{}Expires={expires}&Signature={signature}&Key-Pair-Id={}",
+            query_separator(url),
+            self.key_pair_id
+        ))
+    }
+
+    /// Sign a URL with a custom policy. The resource of the policy may
+    /// contain wildcards, in which case the signed query string can be
+    /// reused for every URL matching the resource.
+    pub fn sign_custom_url(
+        &self,
+        url: &str,
+        policy: &CloudFrontPolicy,
+    ) -> Result<String, SignerError> {
+        let policy = policy.to_json()?;
+        let signature = self.sign_policy(&policy);
+        Ok(format!(
+            "{url}{}Policy={}&Signature={signature}&Key-Pair-Id={}",
+            query_separator(url),
+            url_safe_base64(policy.as_bytes()),
+            self.key_pair_id
+        ))
+    }
+
+    /// Create the signed cookies that grant access to every resource that
+    /// matches the custom policy.
+    pub fn signed_cookies(&self, policy: &CloudFrontPolicy) -> Result<SignedCookies, SignerError> {
+        let policy = policy.to_json()?;
+        let signature = self.sign_policy(&policy);
+        Ok(SignedCookies {
+            policy: url_safe_base64(policy.as_bytes()),
+            signature,
+            key_pair_id: self.key_pair_id.clone(),
+        })
+    }
+
+    fn sign_policy(&self, policy: &str) -> String {
+        let signature = self.signing_key.sign(policy.as_bytes());
+        url_safe_base64(&signature.to_bytes())
+    }
+
+    fn resolve_url(&self, path: &str) -> Result<String, SignerError> {
+        if path.starts_with("https://") || path.starts_with("http://") {
+            return Ok(path.to_string());
+        }
+        if let Some(("s3" | "s3a" | "s3n", _)) = path.split_once("://") {
+            let s3_uri = path.parse::<S3Uri>()?;
+            return Ok(format!("{}/{}", self.domain, encode_key(s3_uri.key())));
+        }
+        if path.contains("://") {
+            return Err(SignerError::uri_parse_error(format!(
+                "Unsupported URI scheme. CloudFront paths should be `https` URLs, `s3` URIs or paths relative to the distribution. Received URI: `{path}`."
+            )));
+        }
+        Ok(format!("{}/{}", self.domain, path.trim_start_matches('/')))
+    }
+}
+
+impl Debug for CloudFrontSigner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CloudFrontSigner")
+            .field("key_pair_id", &self.key_pair_id)
+            .field("domain", &self.domain)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait::async_trait]
+impl CloudFileSigner for CloudFrontSigner {
+    async fn sign(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expiration: Duration,
+        permission: Permission,
+    ) -> Result<PresignedUrl, SignerError> {
+        instrument_sign(Provider::CloudFront, permission, expiration, async {
+            if permission != Permission::Read {
+                return Err(SignerError::permission_not_supported(
+                    "CloudFront signed URLs only support read permissions",
+                ));
+            }
+
+            let url = self.resolve_url(path)?;
+            let policy = CloudFrontPolicy::new(url.clone(), valid_from + expiration)
+                .with_start_time(valid_from);
+            let signed_url = self.sign_custom_url(&url, &policy)?;
+            Ok(PresignedUrl::new(signed_url, valid_from, expiration)
+                .with_provider(Provider::CloudFront)
+                .with_permission(Permission::Read))
+        })
+        .await
+    }
+}
+
+/// A custom policy for CloudFront signed URLs and signed cookies.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CloudFrontPolicy {
+    resource: String,
+    date_less_than: SystemTime,
+    date_greater_than: Option<SystemTime>,
+    ip_address: Option<String>,
+}
+
+impl CloudFrontPolicy {
+    /// Create a policy that grants access to `resource` until `expires`.
+    /// The resource may contain `*` and `?` wildcards, for example
+    /// `https://d111111abcdef8.cloudfront.net/images/*`.
+    pub fn new(resource: impl Into<String>, expires: SystemTime) -> Self {
+        Self {
+            resource: resource.into(),
+            date_less_than: expires,
+            date_greater_than: None,
+            ip_address: None,
+        }
+    }
+
+    /// Only grant access from `start_time` onwards.
+    #[must_use]
+    pub fn with_start_time(mut self, start_time: SystemTime) -> Self {
+        self.date_greater_than = Some(start_time);
+        self
+    }
+
+    /// Only grant access to clients in the given IP range, in CIDR
+    /// notation, for example `192.0.2.0/24`.
+    #[must_use]
+    pub fn with_ip_range(mut self, cidr: impl Into<String>) -> Self {
+        self.ip_address = Some(cidr.into());
+        self
+    }
+
+    /// Return the resource the policy grants access to.
+    #[must_use]
+    pub fn resource(&self) -> &str {
+        &self.resource
+    }
+
+    /// Render the policy as the JSON document CloudFront expects.
+    pub fn to_json(&self) -> Result<String, SignerError> {
+        let mut condition = format!(
+            r#""DateLessThan":{{"AWS:EpochTime":{}}}"#,
+            epoch_seconds(self.date_less_than)?
+        );
+        if let Some(start_time) = self.date_greater_than {
+            condition.push_str(&format!(
+                r#","DateGreaterThan":{{"AWS:EpochTime":{}}}"#,
+                epoch_seconds(start_time)?
+            ));
+        }
+        if let Some(ip_address) = &self.ip_address {
+            condition.push_str(&format!(
+                r#","IpAddress":{{"AWS:SourceIp":"{}"}}"#,
+                json_escape(ip_address)
+            ));
+        }
+        Ok(format!(
+            r#"{{"Statement":[{{"Resource":"{}","Condition":{{{condition}}}}}]}}"#,
+            json_escape(&self.resource)
+        ))
+    }
+}
+
+/// The signed cookies that grant access to the resources of a custom
+/// policy.
+///
+/// The `Debug` representation leaves out the signature.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct SignedCookies {
+    policy: String,
+    signature: String,
+    key_pair_id: String,
+}
+
+impl SignedCookies {
+    /// Return the value of the `CloudFront-Policy` cookie.
+    #[must_use]
+    pub fn policy(&self) -> &str {
+        &self.policy
+    }
+
+    /// Return the value of the `CloudFront-Signature` cookie.
+    #[must_use]
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    /// Return the value of the `CloudFront-Key-Pair-Id` cookie.
+    #[must_use]
+    pub fn key_pair_id(&self) -> &str {
+        &self.key_pair_id
+    }
+
+    /// Return the names and values of the three cookies.
+    #[must_use]
+    pub fn cookies(&self) -> [(&'static str, &str); 3] {
+        [
+            ("CloudFront-Policy", &self.policy),
+            ("CloudFront-Signature", &self.signature),
+            ("CloudFront-Key-Pair-Id", &self.key_pair_id),
+        ]
+    }
+}
+
+impl Debug for SignedCookies {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SignedCookies")
+            .field("policy", &self.policy)
+            .field("key_pair_id", &self.key_pair_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Base64 encode with the character substitutions CloudFront expects in
+/// URLs and cookies.
+fn url_safe_base64(bytes: &[u8]) -> String {
+    STANDARD
+        .encode(bytes)
+        .replace('+', "-")
+        .replace('=', "_")
+        .replace('/', "~")
+}
+
+fn json_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::UNIX_EPOCH;
+
+    use crate::error::SignerErrorKind;
+
+    use super::*;
+
+    const PRIVATE_KEY: &str = include_str!("../../tests/fixtures/cloudfront_private_key.pem");
+    const DOMAIN: &str = "https://d111111abcdef8.cloudfront.net";
+    const KEY_PAIR_ID: &str = "K2JCJMDEHXQW5F";
+
+    const CUSTOM_POLICY: &str = "eyJTdGF0ZW1lbnQiOlt7IlJlc291cmNlIjoiaHR0cHM6Ly9kMTExMTExYWJjZGVmOC5jbG91ZGZyb250Lm5ldC9pbWFnZXMvKiIsIkNvbmRpdGlvbiI6eyJEYXRlTGVzc1RoYW4iOnsiQVdTOkVwb2NoVGltZSI6MTcwNDA2NzIwMH0sIkRhdGVHcmVhdGVyVGhhbiI6eyJBV1M6RXBvY2hUaW1lIjoxNzA0MDYzNjAwfSwiSXBBZGRyZXNzIjp7IkFXUzpTb3VyY2VJcCI6IjE5Mi4wLjIuMC8yNCJ9fX1dfQ__";
+    const CUSTOM_POLICY_SIGNATURE: &str = "mcypjeyGWhvq-zw4gQyfE7644RByBCxP8gr81qy3e5CyuqQ9y-yJCjOgu62-TX4c-apMTT8XkncKsyhiDBPsZAZqKECRP4JC6ktWNlJe-fJnGlXby2Bmp0myH3HK7SVHG2ynCWkJqNMjMpLA4bNDGh5Oo-EEjHGv0CkooMX6I-ItgGJpf89faOb0RU9E1ImOZFk3JvkUamIyj2Y4m7DBRIgEx2smTx6PBKrYFN1vF-YqEFncUv3S~PdIYKdQpzjpdOD1FTY-dKPl1GR2lY7h7o9gxJ0ztu~MiygTVI4p5UCl8kPDwQh0ywQ7qIY8VnYuQbnzHUTOmW1fxq9zoP~EMQ__";
+
+    fn signer() -> CloudFrontSigner {
+        CloudFrontSigner::from_pem(KEY_PAIR_ID, DOMAIN, PRIVATE_KEY).unwrap()
+    }
+
+    fn epoch(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn custom_policy() -> CloudFrontPolicy {
+        CloudFrontPolicy::new(format!("{DOMAIN}/images/*"), epoch(1704067200))
+            .with_start_time(epoch(1704063600))
+            .with_ip_range("192.0.2.0/24")
+    }
+
+    #[test]
+    fn canned_policy_url() {
+        let url = signer()
+            .sign_canned_url(&format!("{DOMAIN}/image.jpg"), epoch(1704067200))
+            .unwrap();
+        assert_eq!(
+            url,
+            "https://d111111abcdef8.cloudfront.net/image.jpg?Expires=1704067200&Signature=VqvYUHRodtdfTYwAnPXaMYJwNk8YIh6fXzvyyFv9Lmor-JO0OEQeoiqbPxVqdNELQ0DvuZSTayzj8QO4EXsyznA09moyu9cJ4NPP2WfaB65JPQ0SwMMQdjq~B1tPJORTG~2kJvj2ZLAwRR8aCJ0TDqzhttH~Q3krR4W6dglcpdLJIJ9fOrJ96I8LiODg9xv4mRYuiI3OFnmEPaUZ~x5KT5W6rB~6Rit6zyWNyX5x76D2Fs1ivY1z8WKXM-xl6Kj06XzOdcsL91eF1cUPLXsmsZpCuwug~H3OYusTrac4~ZDUV7PdwTNfNsZnqiNiUgwc7ngWe-SIdHsw~nvEum2KdQ__&Key-Pair-Id=K2JCJMDEHXQW5F"
+        );
+    }
+
+    #[test]
+    fn custom_policy_json() {
+        assert_eq!(
+            custom_policy().to_json().unwrap(),
+            r#"{"Statement":[{"Resource":"https://d111111abcdef8.cloudfront.net/images/*","Condition":{"DateLessThan":{"AWS:EpochTime":1704067200},"DateGreaterThan":{"AWS:EpochTime":1704063600},"IpAddress":{"AWS:SourceIp":"192.0.2.0/24"}}}]}"#
+        );
+    }
+
+    #[test]
+    fn custom_policy_url_with_wildcard_resource() {
+        let url = signer()
+            .sign_custom_url(&format!("{DOMAIN}/images/cat.jpg"), &custom_policy())
+            .unwrap();
+        assert_eq!(
+            url,
+            format!("{DOMAIN}/images/cat.jpg?Policy={CUSTOM_POLICY}&Signature={CUSTOM_POLICY_SIGNATURE}&Key-Pair-Id={KEY_PAIR_ID}")
+        );
+    }
+
+    #[test]
+    fn custom_policy_url_with_existing_query() {
+        let url = signer()
+            .sign_custom_url(
+                &format!("{DOMAIN}/images/cat.jpg?size=large"),
+                &custom_policy(),
+            )
+            .unwrap();
+        assert!(url.starts_with(&format!("{DOMAIN}/images/cat.jpg?size=large&Policy=")));
+    }
+
+    #[test]
+    fn signed_cookies() {
+        let cookies = signer().signed_cookies(&custom_policy()).unwrap();
+        assert_eq!(
+            cookies.cookies(),
+            [
+                ("CloudFront-Policy", CUSTOM_POLICY),
+                ("CloudFront-Signature", CUSTOM_POLICY_SIGNATURE),
+                ("CloudFront-Key-Pair-Id", KEY_PAIR_ID),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_paths() {
+        let signer = signer();
+        assert_eq!(
+            signer.resolve_url("https://example.com/key").unwrap(),
+            "https://example.com/key"
+        );
+        assert_eq!(
+            signer
+                .resolve_url("s3://bucket/reports/Q1 2024.csv")
+                .unwrap(),
+            "https://d111111abcdef8.cloudfront.net/reports/Q1%202024.csv"
+        );
+        assert_eq!(
+            signer.resolve_url("/images/cat.jpg").unwrap(),
+            "https://d111111abcdef8.cloudfront.net/images/cat.jpg"
+        );
+        assert_eq!(
+            signer.resolve_url("gs://bucket/key").unwrap_err().kind(),
+            SignerErrorKind::CloudUriParseError
+        );
+    }
+
+    #[tokio::test]
+    async fn sign_s3_uri() {
+        let presigned_url = signer()
+            .sign(
+                "s3://bucket/reports/Q1 2024.csv",
+                epoch(1704063600),
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            presigned_url.expose_url(),
+            "https://d111111abcdef8.cloudfront.net/reports/Q1%202024.csv?Policy=eyJTdGF0ZW1lbnQiOlt7IlJlc291cmNlIjoiaHR0cHM6Ly9kMTExMTExYWJjZGVmOC5jbG91ZGZyb250Lm5ldC9yZXBvcnRzL1ExJTIwMjAyNC5jc3YiLCJDb25kaXRpb24iOnsiRGF0ZUxlc3NUaGFuIjp7IkFXUzpFcG9jaFRpbWUiOjE3MDQwNjcyMDB9LCJEYXRlR3JlYXRlclRoYW4iOnsiQVdTOkVwb2NoVGltZSI6MTcwNDA2MzYwMH19fV19&Signature=fN7f8sWnw14QV2tfMoTkzw4Fx1HFBiKNnQ9Fh~IzB-nDS0rM-myK-YtNk8qbK9dvpRLApDqCbADPH8O74G-8-v7l-cQy7SczxtJxOBA97C-OnBGQO2bs50zMZc5yXRNsu4iGdYT4An9LzXR9ZyzKyj0zygw-Tkvxha7nT72HkBV74LqXYhQ2colD2BAJpcCPKLdoti6vaGlMz862CbVapK2WBO3c3duYU-lneFQuJBHBq7kv0UHBkGFwBXLlmgN4SpeO8Ib~JsiWdgX3P3UgetuSTp8NUC-p4AuDGUqz4q9Ls6kXmEDVHdVE9tTvOKalwQX~YI2wxuGCaQUTYhlPRA__&Key-Pair-Id=K2JCJMDEHXQW5F"
+        );
+        assert_eq!(presigned_url.valid_until(), epoch(1704067200));
+    }
+
+    #[tokio::test]
+    async fn sign_write_not_supported() {
+        let err = signer()
+            .sign(
+                "images/cat.jpg",
+                SystemTime::now(),
+                Duration::from_secs(3600),
+                Permission::Write,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::PermissionNotSupported);
+    }
+
+    #[test]
+    fn invalid_private_key() {
+        let err = CloudFrontSigner::from_pem(KEY_PAIR_ID, DOMAIN, "not a key").unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::SigningError);
+    }
+
+    #[test]
+    fn debug_hides_secrets() {
+        let signer = signer();
+        let debug = format!("{signer:?}");
+        assert!(debug.contains(KEY_PAIR_ID));
+        assert!(!debug.contains("signing_key"));
+
+        let cookies = signer.signed_cookies(&custom_policy()).unwrap();
+        assert!(!format!("{cookies:?}").contains(cookies.signature()));
+    }
+}
@@ -0,0 +1,220 @@
+//! An implementation of the [`CredentialVendor`] trait for Amazon S3.
+
+use std::time::{Duration, SystemTime};
+
+use aws_config::SdkConfig;
+use serde_json::{json, Value};
+
+use crate::credentials::{directory_prefix, AwsCredentials, CredentialVendor, ScopedCredentials};
+use crate::{Permission, SignerError};
+
+use super::uri::S3Uri;
+
+/// The shortest session STS accepts.
+const MIN_SESSION_DURATION: Duration = Duration::from_secs(15 * 60);
+
+/// A vendor of prefix-scoped, temporary credentials for Amazon S3.
+///
+/// Credentials are issued by assuming an IAM role with an inline session
+/// policy that only allows access to objects below the requested prefix.
+/// The effective permissions are the intersection of the role's policies
+/// and the session policy, so the role itself must grant access to the
+/// prefix.
+///
+/// STS issues sessions of at least 15 minutes. Shorter durations are
+/// rounded up.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> Result<(), cloud_file_signer::SignerError> {
+/// use std::time::Duration;
+/// use cloud_file_signer::Permission;
+/// use cloud_file_signer::aws::AwsCredentialVendor;
+/// use cloud_file_signer::credentials::CredentialVendor;
+///
+/// let config = aws_config::load_from_env().await;
+/// let vendor = AwsCredentialVendor::new(&config, "arn:aws:iam::123456789012:role/reader");
+/// let credentials = vendor
+///     .vend("s3://bucket/path/to/table/", Duration::from_secs(3600), Permission::Read)
+///     .await?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AwsCredentialVendor {
+    client: aws_sdk_sts::Client,
+    role_arn: String,
+    session_name: String,
+    region: Option<String>,
+}
+
+impl AwsCredentialVendor {
+    /// Create a new vendor that assumes the role `role_arn`.
+    pub fn new(config: &SdkConfig, role_arn: impl Into<String>) -> Self {
+        Self {
+            client: aws_sdk_sts::Client::new(config),
+            role_arn: role_arn.into(),
+            session_name: String::from("cloud-file-signer"),
+            region: config.region().map(ToString::to_string),
+        }
+    }
+
+    /// Set the session name of the assumed role, which shows up in
+    /// CloudTrail. Defaults to `cloud-file-signer`.
+    #[must_use]
+    pub fn with_session_name(mut self, session_name: impl Into<String>) -> Self {
+        self.session_name = session_name.into();
+        self
+    }
+
+    /// Return the ARN of the role that is assumed.
+    #[must_use]
+    pub fn role_arn(&self) -> &str {
+        &self.role_arn
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialVendor for AwsCredentialVendor {
+    async fn vend(
+        &self,
+        prefix: &str,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Result<ScopedCredentials, SignerError> {
+        let s3_uri = prefix.parse::<S3Uri>()?;
+        let policy = session_policy(s3_uri.bucket(), s3_uri.key(), permission)?;
+        let duration = expires_in.max(MIN_SESSION_DURATION).as_secs();
+
+        let response = self
+            .client
+            .assume_role()
+            .role_arn(&self.role_arn)
+            .role_session_name(&self.session_name)
+            .policy(policy.to_string())
+            .duration_seconds(i32::try_from(duration).unwrap_or(i32::MAX))
+            .send()
+            .await?;
+        let credentials = response.credentials().ok_or_else(|| {
+            SignerError::other_error("AWS STS did not return credentials for the assumed role")
+        })?;
+
+        let mut aws_credentials =
+            AwsCredentials::new(credentials.access_key_id(), credentials.secret_access_key())
+                .with_session_token(credentials.session_token());
+        if let Ok(expires_at) = SystemTime::try_from(*credentials.expiration()) {
+            aws_credentials = aws_credentials.with_expires_at(expires_at);
+        }
+        if let Some(region) = &self.region {
+            aws_credentials = aws_credentials.with_region(region);
+        }
+        Ok(aws_credentials.into())
+    }
+}
+
+/// Build a session policy that grants `permission` on every object below
+/// the directory `prefix` in `bucket`, and allows listing those objects.
+///
+/// `*` and `?` are wildcards in IAM policies, so prefixes containing them
+/// are rejected rather than granting access to more than the prefix.
+fn session_policy(
+    bucket: &str,
+    prefix: &str,
+    permission: Permission,
+) -> Result<Value, SignerError> {
+    if prefix.contains(['*', '?']) {
+        return Err(SignerError::uri_parse_error(format!(
+            "Credentials cannot be scoped to the prefix `{prefix}`: it contains the IAM policy wildcards `*` or `?`."
+        )));
+    }
+    let prefix = directory_prefix(prefix);
+    let object_actions = match permission {
+        Permission::Read => vec!["s3:GetObject", "s3:GetObjectVersion"],
+        Permission::Write => vec![
+            "s3:PutObject",
+            "s3:AbortMultipartUpload",
+            "s3:ListMultipartUploadParts",
+        ],
+    };
+    Ok(json!({
+        "Version": "2012-10-17",
+        "Statement": [
+            {
+                "Effect": "Allow",
+                "Action": object_actions,
+                "Resource": [format!("arn:aws:s3:::{bucket}/{prefix}*")],
+            },
+            {
+                "Effect": "Allow",
+                "Action": ["s3:ListBucket"],
+                "Resource": [format!("arn:aws:s3:::{bucket}")],
+                "Condition": {"StringLike": {"s3:prefix": [format!("{prefix}*")]}},
+            },
+            {
+                "Effect": "Allow",
+                "Action": ["s3:GetBucketLocation"],
+                "Resource": [format!("arn:aws:s3:::{bucket}")],
+            },
+        ],
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_policy() {
+        let policy = session_policy("bucket", "tables/sales/", Permission::Read).unwrap();
+        assert_eq!(
+            policy["Statement"][0]["Action"],
+            json!(["s3:GetObject", "s3:GetObjectVersion"])
+        );
+        assert_eq!(
+            policy["Statement"][0]["Resource"],
+            json!(["arn:aws:s3:::bucket/tables/sales/*"])
+        );
+        assert_eq!(
+            policy["Statement"][1]["Condition"]["StringLike"]["s3:prefix"],
+            json!(["tables/sales/*"])
+        );
+    }
+
+    #[test]
+    fn write_policy() {
+        let policy = session_policy("bucket", "uploads/", Permission::Write).unwrap();
+        let actions = policy["Statement"][0]["Action"].as_array().unwrap();
+        assert!(actions.contains(&json!("s3:PutObject")));
+        assert!(!actions.contains(&json!("s3:GetObject")));
+    }
+
+    #[test]
+    fn policy_escapes_prefix() {
+        let policy = session_policy("bucket", "a\"b/", Permission::Read).unwrap();
+        let reparsed: Value = serde_json::from_str(&policy.to_string()).unwrap();
+        assert_eq!(
+            reparsed["Statement"][0]["Resource"],
+            json!(["arn:aws:s3:::bucket/a\"b/*"])
+        );
+    }
+
+    #[test]
+    fn policy_is_scoped_to_directory() {
+        let policy = session_policy("bucket", "tables/sales", Permission::Read).unwrap();
+        assert_eq!(
+            policy["Statement"][0]["Resource"],
+            json!(["arn:aws:s3:::bucket/tables/sales/*"])
+        );
+        assert_eq!(
+            policy["Statement"][1]["Condition"]["StringLike"]["s3:prefix"],
+            json!(["tables/sales/*"])
+        );
+    }
+
+    #[test]
+    fn policy_rejects_wildcards() {
+        for prefix in ["tables/*/", "tables/sales?/"] {
+            let err = session_policy("bucket", prefix, Permission::Read).unwrap_err();
+            assert_eq!(err.kind(), crate::SignerErrorKind::CloudUriParseError);
+        }
+    }
+}
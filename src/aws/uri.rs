@@ -1,6 +1,7 @@
 use http::Uri;
 use std::str::FromStr;
 
+use crate::encoding::{decode, query_param};
 use crate::error::SignerError;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -8,6 +9,7 @@ pub struct S3Uri {
     bucket: String,
     key: String,
     region: Option<String>,
+    version_id: Option<String>,
 }
 
 impl S3Uri {
@@ -16,23 +18,29 @@ impl S3Uri {
             bucket,
             key,
             region,
+            version_id: None,
         }
     }
 
-    fn from_s3_uri(uri: &Uri) -> Result<Self, SignerError> {
-        let bucket = uri
-            .host()
-            .ok_or(SignerError::uri_parse_error(
-                format!("Invalid URI: Couldn't extract the S3 bucket name. Format the URI as `s3://<bucket_name>/<key>`. Received: {}", uri)
-            ))?;
+    /// Parse a `s3://<bucket>/<key>` URI. The key is taken verbatim, without
+    /// percent-decoding.
+    fn from_s3_uri(uri: &str) -> Result<Self, SignerError> {
+        let (_, rest) = uri.split_once("://").ok_or(SignerError::uri_parse_error(
+            format!("Invalid URI: Couldn't extract the S3 bucket name. Format the URI as `s3://<bucket_name>/<key>`. Received: {}", uri)
+        ))?;
 
-        let key = uri
-            .path()
-            .strip_prefix('/')
+        let (bucket, key) = rest
+            .split_once('/')
             .ok_or(SignerError::uri_parse_error(
                 format!("Invalid URI: Couldn't extract the S3 object key. Format the URI as `s3://<bucket_name>/<key>`. Received: {}", uri)
             ))?;
 
+        if bucket.is_empty() {
+            return Err(SignerError::uri_parse_error(
+                format!("Invalid URI: Couldn't extract the S3 bucket name. Format the URI as `s3://<bucket_name>/<key>`. Received: {}", uri)
+            ));
+        }
+
         Ok(Self::new(bucket.to_string(), key.to_string(), None))
     }
 
@@ -49,11 +57,16 @@ impl S3Uri {
         let _region = cap.get(2).map(|m| m.as_str());
         let prefix = cap.get(1).map(|m| m.as_str());
 
-        if let Some(p) = prefix {
-            Self::parse_virtual_hosted_style_url(uri, p)
+        let s3_uri = if let Some(p) = prefix {
+            Self::parse_virtual_hosted_style_url(uri, p)?
         } else {
-            Self::parse_path_style_url(uri.clone())
-        }
+            Self::parse_path_style_url(uri.clone())?
+        };
+
+        Ok(Self {
+            version_id: query_param(uri, "versionId")?,
+            ..s3_uri
+        })
     }
 
     fn parse_virtual_hosted_style_url(uri: &Uri, bucket: &str) -> Result<Self, SignerError> {
@@ -64,8 +77,9 @@ impl S3Uri {
 
         Ok(Self {
             bucket: bucket.strip_suffix('.').unwrap().to_string(),
-            key: key.to_string(),
+            key: decode(key)?,
             region: None,
+            version_id: None,
         })
     }
 
@@ -82,9 +96,10 @@ impl S3Uri {
         ))?;
 
         Ok(Self {
-            bucket: bucket.to_string(),
-            key: key.to_string(),
+            bucket: decode(bucket)?,
+            key: decode(key)?,
             region: None,
+            version_id: None,
         })
     }
 
@@ -95,18 +110,25 @@ impl S3Uri {
     pub fn key(&self) -> &str {
         &self.key
     }
+
+    pub fn version_id(&self) -> Option<&str> {
+        self.version_id.as_deref()
+    }
 }
 
 impl FromStr for S3Uri {
     type Err = SignerError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(("s3" | "s3a" | "s3n", _)) = s.split_once("://") {
+            return Self::from_s3_uri(s);
+        }
+
         let uri: Uri = s.parse().map_err(|e| {
             SignerError::uri_parse_error(format!("Invalid URI. Cause: {e}. Received URI: `{s}`."))
         })?;
 
         match uri.scheme_str() {
-            Some("s3" | "s3a" | "s3n") => Ok(Self::from_s3_uri(&uri)?),
             Some("http" | "https") => Ok(Self::from_url(&uri)?),
             None => Err(SignerError::uri_parse_error(
                 format!("Invalid URI: missing scheme. The URI should start with `S3`, `S3a`, `S3n`, `http` or `https`. Received URI: `{s}`."))
@@ -120,6 +142,9 @@ impl FromStr for S3Uri {
 
 #[cfg(test)]
 mod test {
+    use proptest::prelude::*;
+
+    use crate::encoding::encode_key;
     use crate::error::SignerErrorKind;
 
     use super::*;
@@ -156,6 +181,36 @@ mod test {
         assert_eq!(s3_uri.key(), "key/nested");
     }
 
+    #[test]
+    fn parse_s3_raw_key() {
+        let uri = "s3://bucket/reports/Q1 2024/naïve+data#1.csv?x=100%25";
+        let s3_uri = S3Uri::from_str(uri).unwrap();
+        assert_eq!(s3_uri.bucket(), "bucket");
+        assert_eq!(s3_uri.key(), "reports/Q1 2024/naïve+data#1.csv?x=100%25");
+    }
+
+    #[test]
+    fn parse_s3_uri_without_key() {
+        let uri = "s3://bucket";
+        let uri_err = S3Uri::from_str(uri).unwrap_err();
+        assert_eq!(uri_err.kind(), SignerErrorKind::CloudUriParseError);
+        assert_eq!(
+            uri_err.message(),
+            "Invalid URI: Couldn't extract the S3 object key. Format the URI as `s3://<bucket_name>/<key>`. Received: s3://bucket"
+        );
+    }
+
+    #[test]
+    fn parse_s3_uri_without_bucket() {
+        let uri = "s3:///key";
+        let uri_err = S3Uri::from_str(uri).unwrap_err();
+        assert_eq!(uri_err.kind(), SignerErrorKind::CloudUriParseError);
+        assert_eq!(
+            uri_err.message(),
+            "Invalid URI: Couldn't extract the S3 bucket name. Format the URI as `s3://<bucket_name>/<key>`. Received: s3:///key"
+        );
+    }
+
     #[test]
     fn parse_http_scheme() {
         let uri = "http://bucket.s3.us-east-1.amazonaws.com/key";
@@ -204,6 +259,47 @@ mod test {
         assert_eq!(s3_uri.key(), "key");
     }
 
+    #[test]
+    fn parse_virtual_hosted_percent_encoded_key() {
+        let uri = "https://bucket.s3.amazonaws.com/reports/Q1%202024/na%C3%AFve%2Bdata%231.csv";
+        let s3_uri = S3Uri::from_str(uri).unwrap();
+        assert_eq!(s3_uri.bucket(), "bucket");
+        assert_eq!(s3_uri.key(), "reports/Q1 2024/naïve+data#1.csv");
+    }
+
+    #[test]
+    fn parse_path_style_percent_encoded_key() {
+        let uri = "https://s3.us-east-1.amazonaws.com/bucket/Q1%202024%2Fdata.csv";
+        let s3_uri = S3Uri::from_str(uri).unwrap();
+        assert_eq!(s3_uri.bucket(), "bucket");
+        assert_eq!(s3_uri.key(), "Q1 2024/data.csv");
+    }
+
+    #[test]
+    fn parse_url_with_version_id() {
+        let uri =
+            "https://bucket.s3.us-east-1.amazonaws.com/key?versionId=3HL4kqtJlcpXroDTDmJ%2Brmf";
+        let s3_uri = S3Uri::from_str(uri).unwrap();
+        assert_eq!(s3_uri.bucket(), "bucket");
+        assert_eq!(s3_uri.key(), "key");
+        assert_eq!(s3_uri.version_id(), Some("3HL4kqtJlcpXroDTDmJ+rmf"));
+    }
+
+    #[test]
+    fn parse_url_without_version_id() {
+        let uri = "https://s3.amazonaws.com/bucket/key?other=value";
+        let s3_uri = S3Uri::from_str(uri).unwrap();
+        assert_eq!(s3_uri.version_id(), None);
+    }
+
+    #[test]
+    fn parse_s3_scheme_keeps_query_in_key() {
+        let uri = "s3://bucket/key?versionId=abc";
+        let s3_uri = S3Uri::from_str(uri).unwrap();
+        assert_eq!(s3_uri.key(), "key?versionId=abc");
+        assert_eq!(s3_uri.version_id(), None);
+    }
+
     #[test]
     fn parse_invalid_uri() {
         let uri = "";
@@ -236,4 +332,19 @@ mod test {
             "Unsupported URI scheme. Supported schemas are `S3`, `S3a`, `S3n`, `http` and `https`. Received scheme: `abfss`."
         );
     }
+
+    proptest! {
+        #[test]
+        fn s3_uri_key_round_trips(key in "\\PC*") {
+            let s3_uri = S3Uri::from_str(&format!("s3://bucket/{key}")).unwrap();
+            prop_assert_eq!(s3_uri.key(), key);
+        }
+
+        #[test]
+        fn url_key_round_trips(key in "\\PC*") {
+            let url = format!("https://bucket.s3.us-east-1.amazonaws.com/{}", encode_key(&key));
+            let s3_uri = S3Uri::from_str(&url).unwrap();
+            prop_assert_eq!(s3_uri.key(), key);
+        }
+    }
 }
@@ -1,20 +1,127 @@
-//! An implementation of the [`CloudFileSigner`] trait for Azure Blob Storage.
+//! Implementations of the [`CloudFileSigner`] and
+//! [`CredentialVendor`] traits for Azure Blob Storage.
 
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
+use azure_core::error::ErrorKind;
+use azure_core::{StatusCode, Url};
 use azure_storage::prelude::*;
+use azure_storage::shared_access_signature::service_sas::{
+    BlobSharedAccessSignature, BlobSignedResource,
+};
+use azure_storage::shared_access_signature::SasToken;
+use azure_storage::StorageCredentialsInner;
 use azure_storage_blobs::prelude::*;
+use futures::{stream, StreamExt};
 
-use crate::{CloudFileSigner, Permission, PresignedUrl, SignerError};
+use crate::clock::{Clock, SystemClock};
+use crate::credentials::{
+    directory_prefix, AzureSasCredentials, CredentialVendor, ScopedCredentials,
+};
+use crate::telemetry::{instrument_sign, record_bucket};
+use crate::time::quantize_window;
+use crate::validation::{SigningWindow, ONE_WEEK};
+use crate::{
+    CloudFileSigner, Permission, PresignedUrl, Provider, SignOptions, SignerError, ValidityLimits,
+    SIGN_MANY_CONCURRENCY,
+};
 mod uri;
+mod verify;
+
+pub use verify::SasVerifier;
 
 use self::uri::AzureUri;
 
+/// The limits on the validity window of Azure Blob Storage SAS URLs.
+///
+/// A SAS signed with a user delegation key cannot outlive the key, which is
+/// valid for at most one week. A SAS signed with the account key has no
+/// upper bound, but the same limit is applied to keep long-lived SAS URLs
+/// from being handed out by accident.
+pub const BLOB_VALIDITY_LIMITS: ValidityLimits =
+    ValidityLimits::new("Azure Blob Storage", ONE_WEEK, ONE_WEEK);
+
 /// A signer for Azure Blob Storage.
 #[derive(Debug, Clone)]
 pub struct AbfsFileSigner {
     storage_account: String,
     client_builder: ClientBuilder,
+    storage_credentials: Option<StorageCredentials>,
+    sas_normalization: Option<SasNormalization>,
+    clock: Arc<dyn Clock>,
+    clock_skew: Duration,
+    sign_many_concurrency: usize,
+}
+
+/// A normalized, cache friendly layout for SAS URLs.
+///
+/// CDNs in front of Blob Storage, such as Azure Front Door, use the full URL
+/// including the SAS query string as cache key. With a normalized layout the
+/// query parameters are always emitted in the same (alphabetical) order, and
+/// the start (`st`) and expiry (`se`) times can be aligned to fixed time
+/// buckets. Identical requests within the same bucket then yield
+/// byte-identical URLs.
+///
+/// # Example
+/// ```no_run
+/// use std::time::Duration;
+/// use azure_storage::StorageCredentials;
+/// use cloud_file_signer::azure::{AbfsFileSigner, SasNormalization};
+///
+/// let signer = AbfsFileSigner::new(
+///     "mystorageaccount",
+///     StorageCredentials::access_key("mystorageaccount", "bXlrZXk="),
+/// )
+/// .with_sas_normalization(
+///     SasNormalization::new().with_time_bucket(Duration::from_secs(15 * 60)),
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SasNormalization {
+    time_bucket: Option<Duration>,
+}
+
+impl SasNormalization {
+    /// Create a normalization that only fixes the order of the query
+    /// parameters.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Align the start time down and the expiry time up to multiples of
+    /// `bucket`, counted from the Unix epoch. The signed URL is therefore
+    /// valid for at least the requested window. The aligned window is
+    /// checked against [`BLOB_VALIDITY_LIMITS`].
+    #[must_use]
+    pub fn with_time_bucket(mut self, bucket: Duration) -> Self {
+        self.time_bucket = Some(bucket);
+        self
+    }
+
+    /// Return the time bucket the start and expiry times are aligned to.
+    #[must_use]
+    pub fn time_bucket(&self) -> Option<Duration> {
+        self.time_bucket
+    }
+
+    fn window(&self, valid_from: SystemTime, expiration: Duration) -> (SystemTime, Duration) {
+        match self.time_bucket {
+            Some(bucket) => quantize_window(valid_from, expiration, bucket),
+            None => (valid_from, expiration),
+        }
+    }
+
+    fn normalize(&self, url: &mut Url) {
+        let mut params = url
+            .query_pairs()
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect::<Vec<_>>();
+        params.sort();
+        url.query_pairs_mut().clear().extend_pairs(params);
+    }
 }
 
 impl AbfsFileSigner {
@@ -24,14 +131,25 @@ impl AbfsFileSigner {
         storage_credentials: C,
     ) -> Self {
         let storage_account_name = storage_account.into();
-        let client_builder = ClientBuilder::new(storage_account_name.clone(), storage_credentials);
+        let storage_credentials = storage_credentials.into();
+        let client_builder =
+            ClientBuilder::new(storage_account_name.clone(), storage_credentials.clone());
         Self {
             storage_account: storage_account_name,
             client_builder,
+            storage_credentials: Some(storage_credentials),
+            sas_normalization: None,
+            clock: Arc::new(SystemClock),
+            clock_skew: Duration::ZERO,
+            sign_many_concurrency: SIGN_MANY_CONCURRENCY,
         }
     }
 
     /// Create a new signer for Azure Blob Storage with specified client builder.
+    ///
+    /// The account key cannot be read back from a client builder, so a signer
+    /// created this way cannot vend credentials for a prefix below the root
+    /// of a container.
     pub fn from_client_builder<A: Into<String>>(
         storage_account: A,
         client_builder: ClientBuilder,
@@ -40,9 +158,47 @@ impl AbfsFileSigner {
         Self {
             storage_account: storage_account_name,
             client_builder,
+            storage_credentials: None,
+            sas_normalization: None,
+            clock: Arc::new(SystemClock),
+            clock_skew: Duration::ZERO,
+            sign_many_concurrency: SIGN_MANY_CONCURRENCY,
         }
     }
 
+    /// Generate SAS URLs with a normalized, cache friendly layout. See
+    /// [`SasNormalization`] for details.
+    #[must_use]
+    pub fn with_sas_normalization(mut self, normalization: SasNormalization) -> Self {
+        self.sas_normalization = Some(normalization);
+        self
+    }
+
+    /// Backdate the start time (`st`) of SAS URLs by up to `clock_skew`, so
+    /// they are accepted by storage frontends whose clocks are behind. The
+    /// reported [`PresignedUrl::valid_from`] and the expiry time (`se`) are
+    /// not affected.
+    #[must_use]
+    pub fn with_clock_skew(mut self, clock_skew: Duration) -> Self {
+        self.clock_skew = clock_skew;
+        self
+    }
+
+    /// Sign at most `concurrency` URLs concurrently in
+    /// [`CloudFileSigner::sign_many`]. Defaults to [`SIGN_MANY_CONCURRENCY`].
+    #[must_use]
+    pub fn with_sign_many_concurrency(mut self, concurrency: usize) -> Self {
+        self.sign_many_concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Read the current time from `clock` instead of the system time.
+    #[must_use]
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
     /// Return the name of the storage account for which this
     /// signer is configured.
     #[must_use]
@@ -54,66 +210,128 @@ impl AbfsFileSigner {
         self.client_builder.clone()
     }
 
-    async fn sign_read_request(
+    async fn sign_blob(
         &self,
-        uri: &AzureUri,
+        container_client: &ContainerClient,
+        azure_uri: &AzureUri,
         valid_from: SystemTime,
         expiration: Duration,
+        permission: Permission,
+        options: &SignOptions,
     ) -> Result<PresignedUrl, SignerError> {
-        if uri.storage_account() != self.storage_account() {
-            return Err(SignerError::other_error(
-                "Storage account name in URI does not match signer",
+        record_bucket(azure_uri.container());
+        if azure_uri.storage_account() != self.storage_account() {
+            return Err(SignerError::account_mismatch(format!(
+                "Storage account {} in URI does not match signer for storage account {}",
+                azure_uri.storage_account(),
+                self.storage_account()
+            )));
+        }
+        if options.server_side_encryption().is_some() {
+            return Err(SignerError::option_not_supported(
+                "Azure Blob Storage does not support server-side encryption parameters in signed URLs.",
+            ));
+        }
+        // The aligned window is the one that is signed, so it is the one that
+        // has to respect the limits.
+        let (valid_from, expiration) = match &self.sas_normalization {
+            Some(normalization) => normalization.window(valid_from, expiration),
+            None => (valid_from, expiration),
+        };
+        BLOB_VALIDITY_LIMITS.validate_at(valid_from, expiration, self.clock.now())?;
+        let window = BLOB_VALIDITY_LIMITS.backdate(valid_from, expiration, self.clock_skew);
+        let version_id = options.version_id().or(azure_uri.version_id());
+        let snapshot = options.snapshot().or(azure_uri.snapshot());
+        if version_id.is_some() && snapshot.is_some() {
+            return Err(SignerError::option_not_supported(
+                "Azure Blob Storage URLs cannot be pinned to both a version and a snapshot.",
             ));
         }
 
-        let start_time = valid_from;
-        let end_time = start_time + expiration;
+        let blob_client = container_client.blob_client(azure_uri.blob());
+        match permission {
+            Permission::Read => Ok(self
+                .sign_read_request(&blob_client, &window, version_id, snapshot)
+                .await?),
+            Permission::Write if version_id.is_some() || snapshot.is_some() => {
+                Err(SignerError::option_not_supported(
+                    "Azure Blob Storage does not support pinning a version or snapshot for write requests.",
+                ))
+            }
+            Permission::Write => Ok(self
+                .sign_write_request(&blob_client, &window)
+                .await?),
+        }
+    }
+
+    async fn sign_read_request(
+        &self,
+        blob_client: &BlobClient,
+        window: &SigningWindow,
+        version_id: Option<&str>,
+        snapshot: Option<&str>,
+    ) -> Result<PresignedUrl, SignerError> {
+        let start_time = window.signed_from;
+        let end_time = start_time + window.signed_expires_in;
         let permissions = BlobSasPermissions {
             read: true,
             ..Default::default()
         };
 
-        let blob_client = self
-            .client_builder()
-            .blob_client(uri.container(), uri.blob());
         let sas_token = blob_client
             .shared_access_signature(permissions, end_time.into())
             .await?;
         let sas_token = sas_token.start(start_time);
 
-        let signed_url = blob_client.generate_signed_blob_url(&sas_token)?;
-        Ok(PresignedUrl::new(signed_url, valid_from, expiration))
+        // A SAS for the base blob also grants access to its versions and
+        // snapshots, which are selected with an unsigned query parameter.
+        let mut signed_url = blob_client.generate_signed_blob_url(&sas_token)?;
+        if let Some(version_id) = version_id {
+            signed_url
+                .query_pairs_mut()
+                .append_pair("versionid", version_id);
+        }
+        if let Some(snapshot) = snapshot {
+            signed_url
+                .query_pairs_mut()
+                .append_pair("snapshot", snapshot);
+        }
+        if let Some(normalization) = &self.sas_normalization {
+            normalization.normalize(&mut signed_url);
+        }
+        Ok(
+            PresignedUrl::new(signed_url, window.valid_from, window.expires_in)
+                .with_provider(Provider::AzureBlob)
+                .with_permission(Permission::Read),
+        )
     }
 
     async fn sign_write_request(
         &self,
-        uri: &AzureUri,
-        valid_from: SystemTime,
-        expiration: Duration,
+        blob_client: &BlobClient,
+        window: &SigningWindow,
     ) -> Result<PresignedUrl, SignerError> {
-        if uri.storage_account() != self.storage_account() {
-            return Err(SignerError::other_error(
-                "Storage account name in URI does not match signer",
-            ));
-        }
-
-        let start_time = valid_from;
-        let end_time = start_time + expiration;
+        let start_time = window.signed_from;
+        let end_time = start_time + window.signed_expires_in;
         let permissions = BlobSasPermissions {
             write: true,
             ..Default::default()
         };
 
-        let blob_client = self
-            .client_builder()
-            .blob_client(uri.container(), uri.blob());
         let sas_token = blob_client
             .shared_access_signature(permissions, end_time.into())
             .await?;
         let sas_token = sas_token.start(start_time);
 
-        let signed_url = blob_client.generate_signed_blob_url(&sas_token)?;
-        Ok(PresignedUrl::new(signed_url, valid_from, expiration))
+        let mut signed_url = blob_client.generate_signed_blob_url(&sas_token)?;
+        if let Some(normalization) = &self.sas_normalization {
+            normalization.normalize(&mut signed_url);
+        }
+        Ok(
+            PresignedUrl::new(signed_url, window.valid_from, window.expires_in)
+                .with_provider(Provider::AzureBlob)
+                .with_permission(Permission::Write),
+        )
     }
 }
 
@@ -126,21 +344,550 @@ impl CloudFileSigner for AbfsFileSigner {
         expiration: Duration,
         permission: Permission,
     ) -> Result<PresignedUrl, SignerError> {
-        tracing::info!("signing path: {}", path);
-        let azure_uri = path.parse::<AzureUri>()?;
-        match permission {
-            Permission::Read => Ok(self
-                .sign_read_request(&azure_uri, valid_from, expiration)
-                .await?),
-            Permission::Write => Ok(self
-                .sign_write_request(&azure_uri, valid_from, expiration)
-                .await?),
+        self.sign_with_options(
+            path,
+            valid_from,
+            expiration,
+            permission,
+            &SignOptions::default(),
+        )
+        .await
+    }
+
+    async fn sign_with_options(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expiration: Duration,
+        permission: Permission,
+        options: &SignOptions,
+    ) -> Result<PresignedUrl, SignerError> {
+        instrument_sign(Provider::AzureBlob, permission, expiration, async {
+            let azure_uri = path.parse::<AzureUri>()?;
+            let container_client = self
+                .client_builder()
+                .container_client(azure_uri.container());
+            self.sign_blob(
+                &container_client,
+                &azure_uri,
+                valid_from,
+                expiration,
+                permission,
+                options,
+            )
+            .await
+        })
+        .await
+    }
+
+    async fn sign_many(
+        &self,
+        paths: &[&str],
+        valid_from: SystemTime,
+        expiration: Duration,
+        permission: Permission,
+    ) -> Vec<Result<PresignedUrl, SignerError>> {
+        let azure_uris = paths
+            .iter()
+            .map(|path| path.parse::<AzureUri>())
+            .collect::<Vec<_>>();
+
+        // Building a client sets up a new HTTP pipeline, so one client is
+        // shared by all blobs in the same container.
+        let mut container_clients = HashMap::new();
+        for azure_uri in azure_uris.iter().flatten() {
+            container_clients
+                .entry(azure_uri.container())
+                .or_insert_with(|| {
+                    self.client_builder()
+                        .container_client(azure_uri.container())
+                });
         }
+
+        let options = SignOptions::default();
+        let (container_clients, options) = (&container_clients, &options);
+        let requests = azure_uris
+            .iter()
+            .map(|azure_uri| {
+                instrument_sign(Provider::AzureBlob, permission, expiration, async move {
+                    match azure_uri {
+                        Ok(azure_uri) => {
+                            self.sign_blob(
+                                &container_clients[azure_uri.container()],
+                                azure_uri,
+                                valid_from,
+                                expiration,
+                                permission,
+                                options,
+                            )
+                            .await
+                        }
+                        Err(e) => Err(e.clone()),
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        stream::iter(requests)
+            .buffered(self.sign_many_concurrency)
+            .collect()
+            .await
+    }
+
+    fn sign_many_concurrency(&self) -> usize {
+        self.sign_many_concurrency
+    }
+}
+
+/// Vends a directory SAS token (`sr=d`) that is scoped to the prefix, or a
+/// container SAS token if the prefix is the root of the container.
+///
+/// Directory SAS tokens require a storage account with a hierarchical
+/// namespace (Data Lake Storage Gen2), and a signer that was created with
+/// the account key.
+#[async_trait::async_trait]
+impl CredentialVendor for AbfsFileSigner {
+    async fn vend(
+        &self,
+        prefix: &str,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Result<ScopedCredentials, SignerError> {
+        let azure_uri = prefix.parse::<AzureUri>()?;
+        if azure_uri.storage_account() != self.storage_account() {
+            return Err(SignerError::account_mismatch(format!(
+                "Storage account {} in URI does not match signer for storage account {}",
+                azure_uri.storage_account(),
+                self.storage_account()
+            )));
+        }
+        let permissions = match permission {
+            Permission::Read => BlobSasPermissions {
+                read: true,
+                list: true,
+                ..Default::default()
+            },
+            Permission::Write => BlobSasPermissions {
+                write: true,
+                create: true,
+                list: true,
+                ..Default::default()
+            },
+        };
+
+        let now = self.clock.now();
+        BLOB_VALIDITY_LIMITS.validate_at(now, expires_in, now)?;
+        let window = BLOB_VALIDITY_LIMITS.backdate(now, expires_in, self.clock_skew);
+        let (start_time, end_time) = (window.signed_from, now + expires_in);
+        let container_client = self
+            .client_builder()
+            .container_client(azure_uri.container());
+        let directory = directory_prefix(azure_uri.blob());
+        let sas_token = match directory.strip_suffix('/') {
+            None => container_client
+                .shared_access_signature(permissions, end_time.into())
+                .await?
+                .start(start_time)
+                .token(),
+            Some(directory) => self
+                .directory_shared_access_signature(
+                    azure_uri.container(),
+                    directory,
+                    permissions,
+                    end_time,
+                )
+                .await?
+                .start(start_time)
+                .token(),
+        };
+
+        let credentials =
+            AzureSasCredentials::new(self.storage_account(), sas_token).with_expires_at(end_time);
+        Ok(credentials.into())
+    }
+}
+
+impl AbfsFileSigner {
+    /// Create a SAS token for every blob below `directory`, which must not
+    /// end with a `/`.
+    async fn directory_shared_access_signature(
+        &self,
+        container: &str,
+        directory: &str,
+        permissions: BlobSasPermissions,
+        expiry: SystemTime,
+    ) -> Result<BlobSharedAccessSignature, SignerError> {
+        let no_key = || {
+            SignerError::credentials_error(
+                "A directory SAS token can only be signed with the storage account key.",
+            )
+        };
+        let storage_credentials = self.storage_credentials.as_ref().ok_or_else(no_key)?;
+        let credentials = storage_credentials.0.lock().await;
+        let StorageCredentialsInner::Key(account, key) = &*credentials else {
+            return Err(no_key());
+        };
+        let canonicalized_resource = format!("/blob/{account}/{container}/{directory}");
+        Ok(BlobSharedAccessSignature::new(
+            key.clone(),
+            canonicalized_resource,
+            permissions,
+            expiry.into(),
+            BlobSignedResource::Directory,
+        )
+        .signed_directory_depth(directory.split('/').count()))
     }
 }
 
 impl From<azure_storage::Error> for SignerError {
     fn from(e: azure_storage::Error) -> Self {
-        Self::other_error(format!("Azure Storage Error: {}", e))
+        let message = format!("Azure Storage Error: {}", e);
+        let error = match e.kind() {
+            ErrorKind::Credential => Self::credentials_error(message),
+            ErrorKind::Io => Self::network_error(message),
+            ErrorKind::HttpResponse { status, .. } => match status {
+                StatusCode::TooManyRequests | StatusCode::ServiceUnavailable => {
+                    Self::throttled(message)
+                }
+                StatusCode::Unauthorized | StatusCode::Forbidden => {
+                    Self::credentials_error(message)
+                }
+                status if status.is_server_error() => Self::network_error(message),
+                _ => Self::other_error(message),
+            },
+            _ => Self::other_error(message),
+        };
+        error.with_source(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::UNIX_EPOCH;
+
+    use crate::{ManualClock, UrlVerifier};
+
+    use super::*;
+
+    const ACCOUNT: &str = "devstoreaccount1";
+    const KEY: &str =
+        "Eby8vdM02xNOcqFlqUwJPLlmEtlCDXJ1OUzFT50uSRZ6IFsuFq2UVErCz4I6tq/K1SZFPTOtr/KBHBeksoGMGw==";
+    const URI: &str = "abfss://mycontainer@devstoreaccount1.dfs.core.windows.net/path/myfile";
+
+    fn signer() -> AbfsFileSigner {
+        AbfsFileSigner::new(ACCOUNT, StorageCredentials::access_key(ACCOUNT, KEY))
+            .with_clock(ManualClock::new(epoch(1704067200)))
+    }
+
+    fn epoch(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn query_keys(url: &str) -> Vec<String> {
+        Url::parse(url)
+            .unwrap()
+            .query_pairs()
+            .map(|(k, _)| k.into_owned())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn normalized_query_is_sorted() {
+        let signer = signer().with_sas_normalization(SasNormalization::new());
+        let presigned_url = signer
+            .sign_with_options(
+                URI,
+                epoch(1704067260),
+                Duration::from_secs(3600),
+                Permission::Read,
+                &SignOptions::new().with_version_id("2024-01-01T00:00:00.0000000Z"),
+            )
+            .await
+            .unwrap();
+        let keys = query_keys(presigned_url.expose_url());
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(keys, sorted);
+        assert!(keys.contains(&"versionid".to_string()));
+    }
+
+    #[tokio::test]
+    async fn bucketed_urls_are_identical_within_window() {
+        let signer = signer().with_sas_normalization(
+            SasNormalization::new().with_time_bucket(Duration::from_secs(15 * 60)),
+        );
+        let first = signer
+            .sign(
+                URI,
+                epoch(1704067260),
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+            .await
+            .unwrap();
+        let second = signer
+            .sign(
+                URI,
+                epoch(1704067980),
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+            .await
+            .unwrap();
+        assert_eq!(first.expose_url(), second.expose_url());
+        assert_eq!(first.valid_from(), epoch(1704067200));
+        assert_eq!(first.valid_until(), epoch(1704071700));
+    }
+
+    #[tokio::test]
+    async fn normalized_window_respects_max_expiration() {
+        let signer = signer().with_sas_normalization(
+            SasNormalization::new().with_time_bucket(Duration::from_secs(15 * 60)),
+        );
+        // An aligned window of exactly the maximum expiration is signed as is.
+        let presigned_url = signer
+            .sign(URI, epoch(1704067200), ONE_WEEK, Permission::Read)
+            .await
+            .unwrap();
+        assert_eq!(presigned_url.valid_until(), epoch(1704067200) + ONE_WEEK);
+
+        // Aligning an unaligned window would sign more than the maximum.
+        let err = signer
+            .sign(URI, epoch(1704067260), ONE_WEEK, Permission::Read)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), crate::SignerErrorKind::ExpirationTooLong);
+    }
+
+    #[tokio::test]
+    async fn unnormalized_window_is_unchanged() {
+        let presigned_url = signer()
+            .sign(
+                URI,
+                epoch(1704067260),
+                Duration::from_secs(3600),
+                Permission::Write,
+            )
+            .await
+            .unwrap();
+        assert_eq!(presigned_url.valid_from(), epoch(1704067260));
+        assert_eq!(presigned_url.valid_until(), epoch(1704070860));
+    }
+
+    #[tokio::test]
+    async fn clock_skew_backdates_start_time() {
+        let presigned_url = signer()
+            .with_clock_skew(Duration::from_secs(30))
+            .sign(
+                URI,
+                epoch(1704067260),
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+            .await
+            .unwrap();
+        let pairs = Url::parse(presigned_url.expose_url())
+            .unwrap()
+            .query_pairs()
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(pairs["st"], "2024-01-01T00:00:30Z");
+        assert_eq!(pairs["se"], "2024-01-01T01:01:00Z");
+        assert_eq!(presigned_url.valid_from(), epoch(1704067260));
+        assert_eq!(presigned_url.valid_until(), epoch(1704070860));
+    }
+
+    #[tokio::test]
+    async fn expired_window_is_rejected() {
+        let err = signer()
+            .sign(
+                URI,
+                epoch(1704060000),
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), crate::SignerErrorKind::InvalidStartTime);
+    }
+
+    #[tokio::test]
+    async fn signed_urls_verify() {
+        let verifier = SasVerifier::new(ACCOUNT, KEY).unwrap();
+        for permission in [Permission::Read, Permission::Write] {
+            let presigned_url = signer()
+                .sign(
+                    URI,
+                    epoch(1704067260),
+                    Duration::from_secs(3600),
+                    permission,
+                )
+                .await
+                .unwrap();
+            let verification = verifier.verify(&presigned_url).unwrap();
+            assert!(verification.is_authentic());
+            assert_eq!(verification.permission(), Some(permission));
+            assert_eq!(verification.resource(), URI);
+            assert_eq!(verification.valid_until(), presigned_url.valid_until());
+        }
+    }
+
+    #[tokio::test]
+    async fn sign_many_keeps_input_order() {
+        let paths = vec![
+            "abfss://first@devstoreaccount1.dfs.core.windows.net/a",
+            "abfss://second@devstoreaccount1.dfs.core.windows.net/b",
+            "not a uri",
+            "abfss://first@devstoreaccount1.dfs.core.windows.net/c",
+        ];
+        let results = signer()
+            .sign_many(
+                &paths,
+                epoch(1704067260),
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+            .await;
+        assert_eq!(results.len(), 4);
+        assert!(results[0]
+            .as_ref()
+            .unwrap()
+            .expose_url()
+            .starts_with("https://devstoreaccount1.blob.core.windows.net/first/a?"));
+        assert!(results[1]
+            .as_ref()
+            .unwrap()
+            .expose_url()
+            .starts_with("https://devstoreaccount1.blob.core.windows.net/second/b?"));
+        assert!(results[2].is_err());
+        assert!(results[3]
+            .as_ref()
+            .unwrap()
+            .expose_url()
+            .starts_with("https://devstoreaccount1.blob.core.windows.net/first/c?"));
+    }
+
+    async fn vend_sas(signer: &AbfsFileSigner, prefix: &str) -> HashMap<String, String> {
+        let credentials = signer
+            .vend(prefix, Duration::from_secs(3600), Permission::Read)
+            .await
+            .unwrap();
+        let ScopedCredentials::Azure(credentials) = credentials else {
+            panic!("expected Azure credentials");
+        };
+        assert_eq!(credentials.storage_account(), ACCOUNT);
+        assert!(!credentials.sas_token().starts_with('?'));
+        assert_eq!(credentials.expires_at(), Some(epoch(1704070800)));
+        Url::parse(&format!("https://host/?{}", credentials.sas_token()))
+            .unwrap()
+            .query_pairs()
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn vend_directory_sas() {
+        for prefix in ["tables/sales/", "tables/sales"] {
+            let pairs = vend_sas(
+                &signer(),
+                &format!("abfss://mycontainer@devstoreaccount1.dfs.core.windows.net/{prefix}"),
+            )
+            .await;
+            assert_eq!(pairs["sr"], "d");
+            assert_eq!(pairs["sdd"], "2");
+            assert_eq!(pairs["sp"], "rl");
+            assert_eq!(pairs["se"], "2024-01-01T01:00:00Z");
+        }
+
+        // The signature covers the directory, so a SAS for another
+        // directory of the same depth differs.
+        let sales = vend_sas(
+            &signer(),
+            "abfss://mycontainer@devstoreaccount1.dfs.core.windows.net/tables/sales/",
+        )
+        .await;
+        let other = vend_sas(
+            &signer(),
+            "abfss://mycontainer@devstoreaccount1.dfs.core.windows.net/tables/other/",
+        )
+        .await;
+        assert_ne!(sales["sig"], other["sig"]);
+    }
+
+    #[tokio::test]
+    async fn vend_container_sas_for_root() {
+        let pairs = vend_sas(
+            &signer(),
+            "abfss://mycontainer@devstoreaccount1.dfs.core.windows.net/",
+        )
+        .await;
+        assert_eq!(pairs["sr"], "c");
+        assert!(!pairs.contains_key("sdd"));
+    }
+
+    #[tokio::test]
+    async fn vend_directory_sas_requires_account_key() {
+        let builder = ClientBuilder::new(ACCOUNT, StorageCredentials::access_key(ACCOUNT, KEY));
+        let result = AbfsFileSigner::from_client_builder(ACCOUNT, builder)
+            .vend(
+                "abfss://mycontainer@devstoreaccount1.dfs.core.windows.net/tables/",
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+            .await;
+        assert_eq!(
+            result.unwrap_err().kind(),
+            crate::SignerErrorKind::CredentialsError
+        );
+    }
+
+    #[tokio::test]
+    async fn vend_rejects_other_account() {
+        let result = signer()
+            .vend(
+                "abfss://mycontainer@otheraccount.dfs.core.windows.net/tables/",
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+            .await;
+        assert_eq!(
+            result.unwrap_err().kind(),
+            crate::SignerErrorKind::AccountMismatch
+        );
+    }
+
+    #[test]
+    fn classify_storage_errors() {
+        let throttled: SignerError = azure_storage::Error::message(
+            ErrorKind::HttpResponse {
+                status: StatusCode::ServiceUnavailable,
+                error_code: Some("ServerBusy".to_string()),
+            },
+            "busy",
+        )
+        .into();
+        assert_eq!(throttled.kind(), crate::SignerErrorKind::Throttled);
+        assert!(throttled.is_retryable());
+
+        for status in [
+            StatusCode::InternalServerError,
+            StatusCode::BadGateway,
+            StatusCode::GatewayTimeout,
+        ] {
+            let server_error: SignerError = azure_storage::Error::message(
+                ErrorKind::HttpResponse {
+                    status,
+                    error_code: Some("InternalError".to_string()),
+                },
+                "server error",
+            )
+            .into();
+            assert_eq!(server_error.kind(), crate::SignerErrorKind::Network);
+            assert!(server_error.is_retryable());
+        }
+
+        let credentials: SignerError =
+            azure_storage::Error::message(ErrorKind::Credential, "no token").into();
+        assert_eq!(credentials.kind(), crate::SignerErrorKind::CredentialsError);
+        assert!(std::error::Error::source(&credentials).is_some());
     }
 }
@@ -2,13 +2,18 @@ use std::str::FromStr;
 
 use http::Uri;
 
+use crate::encoding::{decode, query_param};
 use crate::SignerError;
 
+const BLOB_ENDPOINT_SUFFIXES: [&str; 2] = [".blob.core.windows.net", ".dfs.core.windows.net"];
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct AzureUri {
     storage_account: String,
     container: String,
     blob: String,
+    version_id: Option<String>,
+    snapshot: Option<String>,
 }
 
 impl AzureUri {
@@ -17,6 +22,8 @@ impl AzureUri {
             storage_account,
             container,
             blob,
+            version_id: None,
+            snapshot: None,
         }
     }
 
@@ -32,6 +39,14 @@ impl AzureUri {
         &self.blob
     }
 
+    pub fn version_id(&self) -> Option<&str> {
+        self.version_id.as_deref()
+    }
+
+    pub fn snapshot(&self) -> Option<&str> {
+        self.snapshot.as_deref()
+    }
+
     pub fn parse_abfss_uri(uri: &Uri) -> Result<Self, SignerError> {
         let storage_account = uri
             .host()
@@ -57,9 +72,33 @@ impl AzureUri {
         Ok(Self::new(
             storage_account.into(),
             container.into(),
-            blob.into(),
+            decode(blob)?,
         ))
     }
+
+    pub fn parse_https_url(uri: &Uri) -> Result<Self, SignerError> {
+        let host = uri.host().unwrap_or_default();
+        let storage_account = BLOB_ENDPOINT_SUFFIXES
+            .iter()
+            .find_map(|suffix| host.strip_suffix(suffix))
+            .filter(|account| !account.is_empty())
+            .ok_or(SignerError::uri_parse_error(format!(
+                "Invalid URI: couldn't extract storage account name. Format the URI as `https://<storage_account>.blob.core.windows.net/<container>/path/to/blob`. Received host: `{host}`."
+            )))?;
+        let (container, blob) = uri
+            .path()
+            .strip_prefix('/')
+            .and_then(|p| p.split_once('/'))
+            .ok_or(SignerError::uri_parse_error(
+                "Invalid URI: couldn't extract container and blob name. Format the URI as `https://<storage_account>.blob.core.windows.net/<container>/path/to/blob`",
+            ))?;
+
+        Ok(Self {
+            version_id: query_param(uri, "versionid")?,
+            snapshot: query_param(uri, "snapshot")?,
+            ..Self::new(storage_account.into(), decode(container)?, decode(blob)?)
+        })
+    }
 }
 
 impl FromStr for AzureUri {
@@ -72,11 +111,12 @@ impl FromStr for AzureUri {
 
         match uri.scheme_str() {
             Some("abfss" | "abfs") => Self::parse_abfss_uri(&uri),
+            Some("https" | "http") => Self::parse_https_url(&uri),
             None => Err(SignerError::uri_parse_error(
-                format!("Invalid URI: missing scheme. The URI should start with `abfs` or `abfss`. Received URI: `{s}`."))
+                format!("Invalid URI: missing scheme. The URI should start with `abfs`, `abfss`, `http` or `https`. Received URI: `{s}`."))
             ),
             Some(unsupported_scheme) => Err(SignerError::uri_parse_error(
-                format!("Unsupported URI scheme. Supported schemas are `abfss` and `abfs`. Received scheme: `{unsupported_scheme}`."),
+                format!("Unsupported URI scheme. Supported schemas are `abfss`, `abfs`, `http` and `https`. Received scheme: `{unsupported_scheme}`."),
             )),
         }
     }
@@ -84,6 +124,9 @@ impl FromStr for AzureUri {
 
 #[cfg(test)]
 mod test {
+    use proptest::prelude::*;
+
+    use crate::encoding::encode_key;
     use crate::error::SignerErrorKind;
 
     use super::*;
@@ -106,11 +149,92 @@ mod test {
         assert_eq!(azure_uri.blob(), "myblob");
     }
 
+    #[test]
+    fn parse_abfss_percent_encoded_blob() {
+        let uri = "abfss://mycontainer@mystorageaccount.dfs.core.windows.net/Q1%202024/na%C3%AFve%2Bdata%231.csv";
+        let azure_uri = AzureUri::from_str(uri).unwrap();
+        assert_eq!(azure_uri.blob(), "Q1 2024/naïve+data#1.csv");
+    }
+
+    #[test]
+    fn parse_blob_url() {
+        let uri = "https://mystorageaccount.blob.core.windows.net/mycontainer/path/myblob";
+        let azure_uri = AzureUri::from_str(uri).unwrap();
+        assert_eq!(azure_uri.storage_account(), "mystorageaccount");
+        assert_eq!(azure_uri.container(), "mycontainer");
+        assert_eq!(azure_uri.blob(), "path/myblob");
+    }
+
+    #[test]
+    fn parse_dfs_url() {
+        let uri = "https://mystorageaccount.dfs.core.windows.net/mycontainer/myblob";
+        let azure_uri = AzureUri::from_str(uri).unwrap();
+        assert_eq!(azure_uri.storage_account(), "mystorageaccount");
+        assert_eq!(azure_uri.container(), "mycontainer");
+        assert_eq!(azure_uri.blob(), "myblob");
+    }
+
+    #[test]
+    fn parse_blob_url_percent_encoded_blob() {
+        let uri =
+            "https://mystorageaccount.blob.core.windows.net/mycontainer/Q1%202024/data%3F.csv";
+        let azure_uri = AzureUri::from_str(uri).unwrap();
+        assert_eq!(azure_uri.blob(), "Q1 2024/data?.csv");
+    }
+
+    #[test]
+    fn parse_blob_url_with_version_id() {
+        let uri = "https://mystorageaccount.blob.core.windows.net/mycontainer/myblob?versionid=2024-01-01T00%3A00%3A00.0000000Z";
+        let azure_uri = AzureUri::from_str(uri).unwrap();
+        assert_eq!(azure_uri.blob(), "myblob");
+        assert_eq!(azure_uri.version_id(), Some("2024-01-01T00:00:00.0000000Z"));
+        assert_eq!(azure_uri.snapshot(), None);
+    }
+
+    #[test]
+    fn parse_blob_url_with_snapshot() {
+        let uri = "https://mystorageaccount.blob.core.windows.net/mycontainer/myblob?snapshot=2024-01-01T00:00:00.0000000Z";
+        let azure_uri = AzureUri::from_str(uri).unwrap();
+        assert_eq!(azure_uri.blob(), "myblob");
+        assert_eq!(azure_uri.version_id(), None);
+        assert_eq!(azure_uri.snapshot(), Some("2024-01-01T00:00:00.0000000Z"));
+    }
+
+    #[test]
+    fn parse_url_unknown_host() {
+        let uri = "https://bucket.s3.amazonaws.com/mycontainer/myblob";
+        let uri_err = AzureUri::from_str(uri).unwrap_err();
+        assert_eq!(uri_err.kind(), SignerErrorKind::CloudUriParseError);
+        assert_eq!(uri_err.message(), "Invalid URI: couldn't extract storage account name. Format the URI as `https://<storage_account>.blob.core.windows.net/<container>/path/to/blob`. Received host: `bucket.s3.amazonaws.com`.")
+    }
+
     #[test]
     fn parse_unsupported_scheme() {
         let uri = "invalid://mystorageaccount.dfs.core.windows.net/mycontainer/myblob";
         let uri_err = AzureUri::from_str(uri).unwrap_err();
         assert_eq!(uri_err.kind(), SignerErrorKind::CloudUriParseError);
-        assert_eq!(uri_err.message(), "Unsupported URI scheme. Supported schemas are `abfss` and `abfs`. Received scheme: `invalid`.")
+        assert_eq!(uri_err.message(), "Unsupported URI scheme. Supported schemas are `abfss`, `abfs`, `http` and `https`. Received scheme: `invalid`.")
+    }
+
+    proptest! {
+        #[test]
+        fn abfss_blob_round_trips(blob in "\\PC*") {
+            let uri = format!(
+                "abfss://mycontainer@mystorageaccount.dfs.core.windows.net/{}",
+                encode_key(&blob)
+            );
+            let azure_uri = AzureUri::from_str(&uri).unwrap();
+            prop_assert_eq!(azure_uri.blob(), blob);
+        }
+
+        #[test]
+        fn url_blob_round_trips(blob in "\\PC*") {
+            let uri = format!(
+                "https://mystorageaccount.blob.core.windows.net/mycontainer/{}",
+                encode_key(&blob)
+            );
+            let azure_uri = AzureUri::from_str(&uri).unwrap();
+            prop_assert_eq!(azure_uri.blob(), blob);
+        }
     }
 }
@@ -0,0 +1,465 @@
+//! A wrapper signer that caches presigned URLs in memory.
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use lru::LruCache;
+
+use crate::clock::{Clock, SystemClock};
+use crate::{CloudFileSigner, Permission, PresignedUrl, SignOptions, SignerError};
+
+/// The default minimum remaining validity of a cached URL.
+const DEFAULT_MIN_REMAINING_VALIDITY: Duration = Duration::from_secs(5 * 60);
+
+/// A signer that caches presigned URLs in memory.
+///
+/// Presigned URLs are cached by path, permission and [`SignOptions`]. A
+/// cached URL is returned when it covers the whole requested window, from
+/// `valid_from` until `valid_from + expires_in`, and its remaining validity
+/// is at least the configured minimum, which defaults to five minutes.
+/// Otherwise the request is signed by the wrapped signer and the result
+/// replaces the cached URL.
+///
+/// Requests that are relative to the current time ask for a slightly later
+/// window on every call, which no cached URL covers. Wrap the signer in a
+/// [`TimeBucketedSigner`](crate::TimeBucketedSigner) first, which extends
+/// every window to the end of its time bucket, so requests within the same
+/// bucket are served from the cache.
+///
+/// The cache holds a fixed number of URLs and evicts the least recently
+/// used URL when it is full.
+///
+/// # Example
+/// ```rust
+/// use std::num::NonZeroUsize;
+/// use std::time::Duration;
+/// use cloud_file_signer::CachingSigner;
+/// use cloud_file_signer::gcp::CloudCdnSigner;
+///
+/// let cdn_signer = CloudCdnSigner::new("my-key", "https://cdn.example.com", b"secret".to_vec());
+/// let signer = CachingSigner::new(cdn_signer, NonZeroUsize::new(10_000).unwrap())
+///     .with_min_remaining_validity(Duration::from_secs(60));
+/// assert_eq!(signer.stats().hits(), 0);
+/// ```
+#[derive(Debug)]
+pub struct CachingSigner<S> {
+    inner: S,
+    cache: Mutex<LruCache<CacheKey, PresignedUrl>>,
+    min_remaining_validity: Duration,
+    clock: Arc<dyn Clock>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    path: String,
+    permission: Permission,
+    options: SignOptions,
+}
+
+/// Hit and miss statistics of a [`CachingSigner`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CacheStats {
+    hits: u64,
+    misses: u64,
+}
+
+impl CacheStats {
+    /// Return the number of requests that were served from the cache.
+    #[must_use]
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Return the number of requests that were signed by the wrapped
+    /// signer.
+    #[must_use]
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Return the fraction of requests that were served from the cache, or
+    /// `0.0` if there were no requests.
+    #[must_use]
+    pub fn hit_ratio(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+impl<S> CachingSigner<S> {
+    /// Wrap `inner` with a cache that holds at most `capacity` URLs.
+    pub fn new(inner: S, capacity: NonZeroUsize) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(LruCache::new(capacity)),
+            min_remaining_validity: DEFAULT_MIN_REMAINING_VALIDITY,
+            clock: Arc::new(SystemClock),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Set the minimum remaining validity a cached URL must have to be
+    /// returned.
+    #[must_use]
+    pub fn with_min_remaining_validity(mut self, min_remaining_validity: Duration) -> Self {
+        self.min_remaining_validity = min_remaining_validity;
+        self
+    }
+
+    /// Read the current time from `clock` instead of the system time when
+    /// checking whether a cached URL is still fresh.
+    #[must_use]
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Return the minimum remaining validity a cached URL must have to be
+    /// returned.
+    #[must_use]
+    pub fn min_remaining_validity(&self) -> Duration {
+        self.min_remaining_validity
+    }
+
+    /// Return the hit and miss statistics of the cache.
+    #[must_use]
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Return the number of cached URLs.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Return `true` if no URLs are cached.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Remove all cached URLs. The statistics are not reset.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Return a reference to the wrapped signer.
+    #[must_use]
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LruCache<CacheKey, PresignedUrl>> {
+        // The cache is never left in an inconsistent state, so a poisoned
+        // lock can safely be recovered.
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lookup(
+        &self,
+        key: &CacheKey,
+        valid_from: SystemTime,
+        expires_in: Duration,
+    ) -> Option<PresignedUrl> {
+        let mut cache = self.lock();
+        let now = self.clock.now();
+        match cache.get(key) {
+            Some(cached) if !self.is_fresh(cached, now) => {
+                cache.pop(key);
+                None
+            }
+            Some(cached) if covers(cached, valid_from, expires_in) => Some(cached.clone()),
+            _ => None,
+        }
+    }
+
+    fn is_fresh(&self, presigned_url: &PresignedUrl, now: SystemTime) -> bool {
+        presigned_url.valid_from() <= now
+            && presigned_url
+                .valid_until()
+                .duration_since(now)
+                .is_ok_and(|remaining| remaining >= self.min_remaining_validity)
+    }
+}
+
+/// Return `true` if `presigned_url` is valid for the whole window that
+/// starts at `valid_from` and lasts `expires_in`.
+fn covers(presigned_url: &PresignedUrl, valid_from: SystemTime, expires_in: Duration) -> bool {
+    presigned_url.valid_from() <= valid_from
+        && valid_from
+            .checked_add(expires_in)
+            .is_some_and(|valid_until| presigned_url.valid_until() >= valid_until)
+}
+
+#[async_trait::async_trait]
+impl<S: CloudFileSigner> CloudFileSigner for CachingSigner<S> {
+    async fn sign(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Result<PresignedUrl, SignerError> {
+        self.sign_with_options(
+            path,
+            valid_from,
+            expires_in,
+            permission,
+            &SignOptions::default(),
+        )
+        .await
+    }
+
+    async fn sign_with_options(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+        options: &SignOptions,
+    ) -> Result<PresignedUrl, SignerError> {
+        let key = CacheKey {
+            path: path.to_string(),
+            permission,
+            options: options.clone(),
+        };
+        if let Some(presigned_url) = self.lookup(&key, valid_from, expires_in) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(presigned_url);
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let presigned_url = self
+            .inner
+            .sign_with_options(path, valid_from, expires_in, permission, options)
+            .await?;
+        self.lock().put(key, presigned_url.clone());
+        Ok(presigned_url)
+    }
+
+    fn sign_many_concurrency(&self) -> usize {
+        self.inner.sign_many_concurrency()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::MockSigner;
+    use crate::ManualClock;
+
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn signer(capacity: usize) -> CachingSigner<MockSigner> {
+        CachingSigner::new(MockSigner, NonZeroUsize::new(capacity).unwrap())
+    }
+
+    #[tokio::test]
+    async fn returns_cached_url() {
+        let signer = signer(10);
+        let now = SystemTime::now();
+        let first = signer
+            .sign("s3://bucket/key", now, HOUR, Permission::Read)
+            .await
+            .unwrap();
+        let second = signer
+            .sign("s3://bucket/key", now, HOUR, Permission::Read)
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+        assert_eq!(signer.stats().hits(), 1);
+        assert_eq!(signer.stats().misses(), 1);
+        assert_eq!(signer.stats().hit_ratio(), 0.5);
+    }
+
+    #[tokio::test]
+    async fn key_includes_permission_and_options() {
+        let signer = signer(10);
+        let now = SystemTime::now();
+        let read = signer
+            .sign("s3://bucket/key", now, HOUR, Permission::Read)
+            .await
+            .unwrap();
+        let write = signer
+            .sign("s3://bucket/key", now, HOUR, Permission::Write)
+            .await
+            .unwrap();
+        assert_ne!(read, write);
+
+        let versioned = signer
+            .sign_with_options(
+                "s3://bucket/key",
+                now,
+                HOUR,
+                Permission::Read,
+                &SignOptions::new().with_version_id("v1"),
+            )
+            .await
+            .unwrap();
+        assert_ne!(read, versioned);
+        assert_eq!(signer.stats().misses(), 3);
+        assert_eq!(signer.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn resigns_when_remaining_validity_is_too_short() {
+        let signer = signer(10).with_min_remaining_validity(Duration::from_secs(600));
+        let now = SystemTime::now();
+        let first = signer
+            .sign(
+                "s3://bucket/key",
+                now,
+                Duration::from_secs(300),
+                Permission::Read,
+            )
+            .await
+            .unwrap();
+        let second = signer
+            .sign("s3://bucket/key", now, HOUR, Permission::Read)
+            .await
+            .unwrap();
+        let third = signer
+            .sign("s3://bucket/key", now, HOUR, Permission::Read)
+            .await
+            .unwrap();
+        assert_ne!(first, second);
+        assert_eq!(second, third);
+        assert_eq!(signer.stats().misses(), 2);
+    }
+
+    #[tokio::test]
+    async fn resigns_when_window_is_not_covered() {
+        let signer = signer(10);
+        let now = SystemTime::now();
+        signer
+            .sign("s3://bucket/key", now, HOUR, Permission::Read)
+            .await
+            .unwrap();
+
+        // A window inside the cached one is served from the cache.
+        let inside = signer
+            .sign(
+                "s3://bucket/key",
+                now + Duration::from_secs(60),
+                Duration::from_secs(600),
+                Permission::Read,
+            )
+            .await
+            .unwrap();
+        assert_eq!(inside.valid_from(), now);
+        assert_eq!(signer.stats().hits(), 1);
+
+        // Windows that end after or start before the cached one are signed.
+        let longer = signer
+            .sign("s3://bucket/key", now, 24 * HOUR, Permission::Read)
+            .await
+            .unwrap();
+        assert_eq!(longer.valid_until(), now + 24 * HOUR);
+        let earlier = signer
+            .sign(
+                "s3://bucket/key",
+                now - Duration::from_secs(60),
+                HOUR,
+                Permission::Read,
+            )
+            .await
+            .unwrap();
+        assert_eq!(earlier.valid_from(), now - Duration::from_secs(60));
+        assert_eq!(signer.stats().hits(), 1);
+        assert_eq!(signer.stats().misses(), 3);
+    }
+
+    #[tokio::test]
+    async fn resigns_when_not_yet_valid() {
+        let signer = signer(10);
+        let tomorrow = SystemTime::now() + 24 * HOUR;
+        signer
+            .sign("s3://bucket/key", tomorrow, HOUR, Permission::Read)
+            .await
+            .unwrap();
+        signer
+            .sign("s3://bucket/key", tomorrow, HOUR, Permission::Read)
+            .await
+            .unwrap();
+        assert_eq!(signer.stats().hits(), 0);
+    }
+
+    #[tokio::test]
+    async fn resigns_when_clock_moves_past_min_remaining_validity() {
+        let now = SystemTime::now();
+        let clock = Arc::new(ManualClock::new(now));
+        let signer = signer(10).with_clock(clock.clone());
+        signer
+            .sign("s3://bucket/key", now, HOUR, Permission::Read)
+            .await
+            .unwrap();
+        clock.advance(HOUR - Duration::from_secs(301));
+        signer
+            .sign("s3://bucket/key", now, HOUR, Permission::Read)
+            .await
+            .unwrap();
+        assert_eq!(signer.stats().hits(), 1);
+        clock.advance(Duration::from_secs(2));
+        signer
+            .sign("s3://bucket/key", now, HOUR, Permission::Read)
+            .await
+            .unwrap();
+        assert_eq!(signer.stats().misses(), 2);
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used() {
+        let signer = signer(2);
+        let now = SystemTime::now();
+        for path in [
+            "s3://bucket/a",
+            "s3://bucket/b",
+            "s3://bucket/a",
+            "s3://bucket/c",
+        ] {
+            signer
+                .sign(path, now, HOUR, Permission::Read)
+                .await
+                .unwrap();
+        }
+        assert_eq!(signer.len(), 2);
+        assert_eq!(signer.stats().misses(), 3);
+
+        signer
+            .sign("s3://bucket/a", now, HOUR, Permission::Read)
+            .await
+            .unwrap();
+        assert_eq!(signer.stats().hits(), 2);
+        signer
+            .sign("s3://bucket/b", now, HOUR, Permission::Read)
+            .await
+            .unwrap();
+        assert_eq!(signer.stats().misses(), 4);
+    }
+
+    #[tokio::test]
+    async fn clear_empties_cache() {
+        let signer = signer(10);
+        signer
+            .sign("s3://bucket/key", SystemTime::now(), HOUR, Permission::Read)
+            .await
+            .unwrap();
+        assert!(!signer.is_empty());
+        signer.clear();
+        assert!(signer.is_empty());
+    }
+}
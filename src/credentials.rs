@@ -0,0 +1,345 @@
+//! Short-lived credentials that grant scoped access to an object store.
+//!
+//! Sometimes a presigned URL per file is not enough, for example when an
+//! engine has to list and read everything below a prefix. A
+//! [`CredentialVendor`] issues short-lived credentials that are scoped to a
+//! prefix instead. Implementations are provided for Amazon S3
+//! ([`AwsCredentialVendor`](crate::aws::AwsCredentialVendor)), Azure Blob
+//! Storage ([`AbfsFileSigner`](crate::azure::AbfsFileSigner)) and Google
+//! Cloud Storage ([`GcpCredentialVendor`](crate::gcp::GcpCredentialVendor)).
+
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::{Permission, SignerError};
+
+/// A trait for issuing short-lived credentials that are scoped to a prefix
+/// in a cloud object store.
+#[async_trait::async_trait]
+pub trait CredentialVendor: Send + Sync {
+    /// Issue credentials that grant the specified permission on every object
+    /// below `prefix`, such as `s3://bucket/path/to/table/`. The prefix is
+    /// treated as a directory, so `s3://bucket/path/to/table` grants access
+    /// to the same objects. The credentials expire after the specified
+    /// duration, or earlier if the cloud provider imposes a shorter lifetime.
+    async fn vend(
+        &self,
+        prefix: &str,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Result<ScopedCredentials, SignerError>;
+}
+
+#[async_trait::async_trait]
+impl<V: CredentialVendor + ?Sized> CredentialVendor for Box<V> {
+    async fn vend(
+        &self,
+        prefix: &str,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Result<ScopedCredentials, SignerError> {
+        (**self).vend(prefix, expires_in, permission).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<V: CredentialVendor + ?Sized> CredentialVendor for Arc<V> {
+    async fn vend(
+        &self,
+        prefix: &str,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Result<ScopedCredentials, SignerError> {
+        (**self).vend(prefix, expires_in, permission).await
+    }
+}
+
+/// Temporary AWS credentials.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct AwsCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+    region: Option<String>,
+    expires_at: Option<SystemTime>,
+}
+
+impl AwsCredentials {
+    /// Create a new set of AWS credentials.
+    pub fn new(access_key_id: impl Into<String>, secret_access_key: impl Into<String>) -> Self {
+        Self {
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+            session_token: None,
+            region: None,
+            expires_at: None,
+        }
+    }
+
+    /// Set the session token of temporary credentials.
+    #[must_use]
+    pub fn with_session_token(mut self, session_token: impl Into<String>) -> Self {
+        self.session_token = Some(session_token.into());
+        self
+    }
+
+    /// Set the region the credentials are meant to be used in.
+    #[must_use]
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    /// Set the time at which the credentials expire.
+    #[must_use]
+    pub fn with_expires_at(mut self, expires_at: SystemTime) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Return the access key id.
+    #[must_use]
+    pub fn access_key_id(&self) -> &str {
+        &self.access_key_id
+    }
+
+    /// Return the secret access key.
+    #[must_use]
+    pub fn secret_access_key(&self) -> &str {
+        &self.secret_access_key
+    }
+
+    /// Return the session token, if any.
+    #[must_use]
+    pub fn session_token(&self) -> Option<&str> {
+        self.session_token.as_deref()
+    }
+
+    /// Return the region, if any.
+    #[must_use]
+    pub fn region(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+
+    /// Return the time at which the credentials expire, if known.
+    #[must_use]
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.expires_at
+    }
+}
+
+impl Debug for AwsCredentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AwsCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("region", &self.region)
+            .field("expires_at", &self.expires_at)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A shared access signature for an Azure storage account.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct AzureSasCredentials {
+    storage_account: String,
+    sas_token: String,
+    expires_at: Option<SystemTime>,
+}
+
+impl AzureSasCredentials {
+    /// Create a new set of credentials from a SAS token, the query string of
+    /// a SAS URL without the leading `?`.
+    pub fn new(storage_account: impl Into<String>, sas_token: impl Into<String>) -> Self {
+        Self {
+            storage_account: storage_account.into(),
+            sas_token: sas_token.into().trim_start_matches('?').to_string(),
+            expires_at: None,
+        }
+    }
+
+    /// Set the time at which the SAS token expires.
+    #[must_use]
+    pub fn with_expires_at(mut self, expires_at: SystemTime) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Return the name of the storage account.
+    #[must_use]
+    pub fn storage_account(&self) -> &str {
+        &self.storage_account
+    }
+
+    /// Return the SAS token.
+    #[must_use]
+    pub fn sas_token(&self) -> &str {
+        &self.sas_token
+    }
+
+    /// Return the time at which the SAS token expires, if known.
+    #[must_use]
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.expires_at
+    }
+}
+
+impl Debug for AzureSasCredentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AzureSasCredentials")
+            .field("storage_account", &self.storage_account)
+            .field("expires_at", &self.expires_at)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An OAuth 2.0 access token for Google Cloud Storage.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct GcpCredentials {
+    access_token: String,
+    project_id: Option<String>,
+    expires_at: Option<SystemTime>,
+}
+
+impl GcpCredentials {
+    /// Create a new set of credentials from an access token.
+    pub fn new(access_token: impl Into<String>) -> Self {
+        Self {
+            access_token: access_token.into(),
+            project_id: None,
+            expires_at: None,
+        }
+    }
+
+    /// Set the project the credentials are meant to be used in.
+    #[must_use]
+    pub fn with_project_id(mut self, project_id: impl Into<String>) -> Self {
+        self.project_id = Some(project_id.into());
+        self
+    }
+
+    /// Set the time at which the access token expires.
+    #[must_use]
+    pub fn with_expires_at(mut self, expires_at: SystemTime) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Return the access token.
+    #[must_use]
+    pub fn access_token(&self) -> &str {
+        &self.access_token
+    }
+
+    /// Return the project id, if any.
+    #[must_use]
+    pub fn project_id(&self) -> Option<&str> {
+        self.project_id.as_deref()
+    }
+
+    /// Return the time at which the access token expires, if known.
+    #[must_use]
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.expires_at
+    }
+}
+
+impl Debug for GcpCredentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GcpCredentials")
+            .field("project_id", &self.project_id)
+            .field("expires_at", &self.expires_at)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Short-lived credentials for one of the supported object stores.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ScopedCredentials {
+    /// Temporary AWS credentials.
+    Aws(AwsCredentials),
+    /// A shared access signature for an Azure storage account.
+    Azure(AzureSasCredentials),
+    /// An OAuth 2.0 access token for Google Cloud Storage.
+    Gcp(GcpCredentials),
+}
+
+impl ScopedCredentials {
+    /// Return the time at which the credentials expire, if known.
+    #[must_use]
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        match self {
+            ScopedCredentials::Aws(credentials) => credentials.expires_at(),
+            ScopedCredentials::Azure(credentials) => credentials.expires_at(),
+            ScopedCredentials::Gcp(credentials) => credentials.expires_at(),
+        }
+    }
+}
+
+impl From<AwsCredentials> for ScopedCredentials {
+    fn from(credentials: AwsCredentials) -> Self {
+        ScopedCredentials::Aws(credentials)
+    }
+}
+
+impl From<AzureSasCredentials> for ScopedCredentials {
+    fn from(credentials: AzureSasCredentials) -> Self {
+        ScopedCredentials::Azure(credentials)
+    }
+}
+
+impl From<GcpCredentials> for ScopedCredentials {
+    fn from(credentials: GcpCredentials) -> Self {
+        ScopedCredentials::Gcp(credentials)
+    }
+}
+
+/// Normalize the key of a prefix to a directory with a trailing `/`, so
+/// that `tables/sales` does not also match `tables/sales_archive/`. An empty
+/// key stands for the whole bucket and stays empty.
+pub(crate) fn directory_prefix(key: &str) -> String {
+    match key.trim_end_matches('/') {
+        "" => String::new(),
+        directory => format!("{directory}/"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn prefixes_are_directories() {
+        assert_eq!(directory_prefix("tables/sales"), "tables/sales/");
+        assert_eq!(directory_prefix("tables/sales/"), "tables/sales/");
+        assert_eq!(directory_prefix("tables//"), "tables/");
+        assert_eq!(directory_prefix(""), "");
+        assert_eq!(directory_prefix("/"), "");
+    }
+
+    #[test]
+    fn debug_hides_secrets() {
+        let aws = AwsCredentials::new("AKIDEXAMPLE", "wJalrXUtnFEMI")
+            .with_session_token("FwoGZXIvYXdzEJr");
+        let azure = AzureSasCredentials::new("account", "?sv=2022-11-02&sig=c2VjcmV0");
+        let gcp = GcpCredentials::new("ya29.a0AfH6SM").with_project_id("my-project");
+
+        let debug = format!("{aws:?} {azure:?} {gcp:?}");
+        for secret in [
+            "wJalrXUtnFEMI",
+            "FwoGZXIvYXdzEJr",
+            "c2VjcmV0",
+            "ya29.a0AfH6SM",
+        ] {
+            assert!(!debug.contains(secret), "{debug}");
+        }
+        assert!(debug.contains("AKIDEXAMPLE"));
+        assert!(debug.contains("my-project"));
+    }
+
+    #[test]
+    fn sas_token_without_question_mark() {
+        let azure = AzureSasCredentials::new("account", "?sv=2022-11-02&sig=abc");
+        assert_eq!(azure.sas_token(), "sv=2022-11-02&sig=abc");
+    }
+}
@@ -0,0 +1,582 @@
+//! Signing of Delta Lake file actions for the Delta Sharing protocol.
+//!
+//! A Delta Sharing server answers table queries with the files a client has
+//! to read, each with a presigned URL. This module takes the `add`,
+//! `remove` and `cdc` actions from the Delta transaction log, resolves
+//! their paths against the root of the table and signs them with any
+//! [`CloudFileSigner`]. The result is a list of Delta Sharing `file`
+//! actions.
+//!
+//! # Example
+//! ```rust
+//! # async fn example() -> Result<(), cloud_file_signer::SignerError> {
+//! use std::time::{Duration, SystemTime};
+//! use cloud_file_signer::delta_sharing::{DeltaSharingSigner, LogFileAction};
+//! use cloud_file_signer::gcp::CloudCdnSigner;
+//!
+//! let commit = r#"{"add":{"path":"part-00000.parquet","partitionValues":{},"size":1024,"modificationTime":1704067200000,"dataChange":true}}"#;
+//! let actions = LogFileAction::parse_commit(0, commit)?;
+//!
+//! let cdn_signer = CloudCdnSigner::new("my-key", "https://cdn.example.com", b"secret".to_vec());
+//! let signer = DeltaSharingSigner::new(cdn_signer, "https://cdn.example.com/tables/sales");
+//! for file in signer
+//!     .sign_actions(&actions, SystemTime::now(), Duration::from_secs(3600))
+//!     .await
+//! {
+//!     println!("{}", file?.to_json());
+//! }
+//! # Ok(()) }
+//! ```
+
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+use crate::encoding::decode;
+use crate::{CloudFileSigner, Permission, PresignedUrl, SignerError};
+
+/// URI schemes whose object keys are used verbatim. Relative paths in the
+/// Delta log are percent-encoded, so they are decoded before they are
+/// appended to a table root with one of these schemes.
+const RAW_KEY_SCHEMES: [&str; 4] = ["s3", "s3a", "s3n", "gs"];
+
+/// The kind of a Delta log action that references a data file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LogFileActionKind {
+    /// A data file that was added to the table.
+    Add,
+    /// A data file that was removed from the table.
+    Remove,
+    /// A change data file.
+    Cdc,
+}
+
+/// A Delta log action that references a data file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFileAction {
+    kind: LogFileActionKind,
+    path: String,
+    partition_values: BTreeMap<String, Option<String>>,
+    size: Option<i64>,
+    stats: Option<String>,
+    version: i64,
+    timestamp: Option<i64>,
+}
+
+impl LogFileAction {
+    /// Parse the `add`, `remove` and `cdc` actions from the contents of a
+    /// commit file in the `_delta_log` directory, such as
+    /// `00000000000000000001.json`. Other actions are skipped.
+    ///
+    /// The actions are tagged with the commit `version` and with the
+    /// timestamp of the commit, if the commit contains a `commitInfo`
+    /// action.
+    pub fn parse_commit(version: i64, contents: &str) -> Result<Vec<Self>, SignerError> {
+        let lines = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str::<LogLine>(line).map_err(|e| {
+                    SignerError::other_error(format!(
+                        "Invalid Delta log action in version {version}. Cause: {e}"
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let commit_timestamp = lines
+            .iter()
+            .find_map(|line| line.commit_info.as_ref().and_then(|c| c.timestamp));
+
+        let actions = lines
+            .into_iter()
+            .flat_map(|line| {
+                [
+                    line.add.map(|file| (LogFileActionKind::Add, file)),
+                    line.remove.map(|file| (LogFileActionKind::Remove, file)),
+                    line.cdc.map(|file| (LogFileActionKind::Cdc, file)),
+                ]
+            })
+            .flatten()
+            .map(|(kind, file)| Self {
+                kind,
+                timestamp: commit_timestamp
+                    .or(file.modification_time)
+                    .or(file.deletion_timestamp),
+                path: file.path,
+                partition_values: file.partition_values,
+                size: file.size,
+                stats: file.stats,
+                version,
+            })
+            .collect();
+        Ok(actions)
+    }
+
+    /// Return the kind of the action.
+    #[must_use]
+    pub fn kind(&self) -> LogFileActionKind {
+        self.kind
+    }
+
+    /// Return the path of the data file as written in the Delta log. The
+    /// path is either relative to the table root or absolute.
+    #[must_use]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Return the partition values of the data file.
+    #[must_use]
+    pub fn partition_values(&self) -> &BTreeMap<String, Option<String>> {
+        &self.partition_values
+    }
+
+    /// Return the size of the data file in bytes, if known.
+    #[must_use]
+    pub fn size(&self) -> Option<i64> {
+        self.size
+    }
+
+    /// Return the statistics of the data file, if any.
+    #[must_use]
+    pub fn stats(&self) -> Option<&str> {
+        self.stats.as_deref()
+    }
+
+    /// Return the version of the commit that contains the action.
+    #[must_use]
+    pub fn version(&self) -> i64 {
+        self.version
+    }
+
+    /// Return the timestamp of the action in milliseconds since the Unix
+    /// epoch, if known.
+    #[must_use]
+    pub fn timestamp(&self) -> Option<i64> {
+        self.timestamp
+    }
+}
+
+/// A Delta Sharing `file` action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileAction {
+    url: String,
+    id: String,
+    partition_values: BTreeMap<String, Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<String>,
+    version: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<i64>,
+    expiration_timestamp: i64,
+}
+
+impl FileAction {
+    fn new(action: &LogFileAction, presigned_url: PresignedUrl) -> Self {
+        Self {
+            id: file_id(&action.path),
+            partition_values: action.partition_values.clone(),
+            size: action.size,
+            stats: action.stats.clone(),
+            version: action.version,
+            timestamp: action.timestamp,
+            expiration_timestamp: epoch_millis(presigned_url.valid_until()),
+            url: presigned_url.into_string(),
+        }
+    }
+
+    /// Return the presigned URL of the data file.
+    #[must_use]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Return the identifier of the data file. The identifier is derived
+    /// from the path in the Delta log, so it is stable across requests.
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Return the partition values of the data file.
+    #[must_use]
+    pub fn partition_values(&self) -> &BTreeMap<String, Option<String>> {
+        &self.partition_values
+    }
+
+    /// Return the size of the data file in bytes, if known.
+    #[must_use]
+    pub fn size(&self) -> Option<i64> {
+        self.size
+    }
+
+    /// Return the statistics of the data file, if any.
+    #[must_use]
+    pub fn stats(&self) -> Option<&str> {
+        self.stats.as_deref()
+    }
+
+    /// Return the table version the data file belongs to.
+    #[must_use]
+    pub fn version(&self) -> i64 {
+        self.version
+    }
+
+    /// Return the timestamp of the action in milliseconds since the Unix
+    /// epoch, if known.
+    #[must_use]
+    pub fn timestamp(&self) -> Option<i64> {
+        self.timestamp
+    }
+
+    /// Return the time at which the URL expires, in milliseconds since the
+    /// Unix epoch.
+    #[must_use]
+    pub fn expiration_timestamp(&self) -> i64 {
+        self.expiration_timestamp
+    }
+
+    /// Render the action as a line of a Delta Sharing query response, in
+    /// the form `{"file":{...}}`.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct Line<'a> {
+            file: &'a FileAction,
+        }
+        serde_json::to_string(&Line { file: self })
+            .expect("a file action only contains strings, numbers and maps with string keys")
+    }
+}
+
+/// A signer for the data files of a Delta table.
+#[derive(Debug, Clone)]
+pub struct DeltaSharingSigner<S> {
+    signer: S,
+    table_root: String,
+}
+
+impl<S: CloudFileSigner> DeltaSharingSigner<S> {
+    /// Create a new signer for the Delta table at `table_root`, for example
+    /// `s3://bucket/path/to/table`.
+    pub fn new(signer: S, table_root: impl Into<String>) -> Self {
+        Self {
+            signer,
+            table_root: table_root.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Return the root URI of the table.
+    #[must_use]
+    pub fn table_root(&self) -> &str {
+        &self.table_root
+    }
+
+    /// Resolve a path from the Delta log against the table root. Absolute
+    /// paths are returned as is.
+    pub fn resolve(&self, path: &str) -> Result<String, SignerError> {
+        if path.contains("://") {
+            return Ok(path.to_string());
+        }
+        let scheme = self.table_root.split_once("://").map(|(scheme, _)| scheme);
+        let path = match scheme {
+            Some(scheme) if RAW_KEY_SCHEMES.contains(&scheme) => decode(path)?,
+            _ => path.to_string(),
+        };
+        Ok(format!(
+            "{}/{}",
+            self.table_root,
+            path.trim_start_matches('/')
+        ))
+    }
+
+    /// Sign the data files referenced by `actions` for reading. The results
+    /// are returned in the order of `actions`.
+    pub async fn sign_actions(
+        &self,
+        actions: &[LogFileAction],
+        valid_from: SystemTime,
+        expires_in: Duration,
+    ) -> Vec<Result<FileAction, SignerError>> {
+        let resolved = actions
+            .iter()
+            .map(|action| self.resolve(&action.path))
+            .collect::<Vec<_>>();
+        let paths = resolved
+            .iter()
+            .filter_map(|path| path.as_deref().ok())
+            .collect::<Vec<_>>();
+        let presigned_urls = self
+            .signer
+            .sign_many(&paths, valid_from, expires_in, Permission::Read)
+            .await;
+        // The URLs can only be matched to their actions by position.
+        let (path_count, signed_count) = (paths.len(), presigned_urls.len());
+        let mut presigned_urls = presigned_urls.into_iter();
+
+        actions
+            .iter()
+            .zip(resolved)
+            .map(|(action, path)| {
+                path?;
+                if signed_count != path_count {
+                    return Err(SignerError::other_error(format!(
+                        "The signer returned {signed_count} URLs for {path_count} paths."
+                    )));
+                }
+                let presigned_url = presigned_urls.next().ok_or_else(|| {
+                    SignerError::other_error("The signer returned too few URLs.")
+                })??;
+                Ok(FileAction::new(action, presigned_url))
+            })
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LogLine {
+    add: Option<LogFile>,
+    remove: Option<LogFile>,
+    cdc: Option<LogFile>,
+    commit_info: Option<CommitInfo>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LogFile {
+    path: String,
+    #[serde(default)]
+    partition_values: BTreeMap<String, Option<String>>,
+    size: Option<i64>,
+    stats: Option<String>,
+    modification_time: Option<i64>,
+    deletion_timestamp: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct CommitInfo {
+    timestamp: Option<i64>,
+}
+
+fn file_id(path: &str) -> String {
+    format!("{:x}", Sha1::digest(path.as_bytes()))
+}
+
+fn epoch_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_millis()).unwrap_or(i64::MAX))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::SignerErrorKind;
+    use crate::testing::{FailingSigner, MockSigner};
+
+    use super::*;
+
+    const VERSION_0: &str =
+        include_str!("../tests/fixtures/delta_table/_delta_log/00000000000000000000.json");
+    const VERSION_1: &str =
+        include_str!("../tests/fixtures/delta_table/_delta_log/00000000000000000001.json");
+
+    /// The query string `MockSigner` appends to URLs signed by the tests.
+    const QUERY: &str = "?permission=read&valid_from=1704067200&expires_in=3600";
+
+    fn epoch(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn parse_commits() {
+        let actions = LogFileAction::parse_commit(0, VERSION_0).unwrap();
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].kind(), LogFileActionKind::Add);
+        assert_eq!(
+            actions[1].path(),
+            "country=United%20States/part-00000-5e6f7a8b-c000.snappy.parquet"
+        );
+        assert_eq!(
+            actions[1].partition_values()["country"].as_deref(),
+            Some("United States")
+        );
+        assert_eq!(actions[0].timestamp(), Some(1704067200000));
+
+        let actions = LogFileAction::parse_commit(1, VERSION_1).unwrap();
+        let kinds = actions.iter().map(LogFileAction::kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                LogFileActionKind::Remove,
+                LogFileActionKind::Add,
+                LogFileActionKind::Cdc
+            ]
+        );
+        assert!(actions.iter().all(|a| a.version() == 1));
+        assert!(actions.iter().all(|a| a.timestamp() == Some(1704153600000)));
+    }
+
+    #[test]
+    fn parse_invalid_commit() {
+        let err = LogFileAction::parse_commit(3, "{\"add\": 1}").unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::Other);
+    }
+
+    #[test]
+    fn resolve_paths() {
+        let s3 = DeltaSharingSigner::new(MockSigner, "s3://bucket/tables/sales/");
+        assert_eq!(
+            s3.resolve("country=United%20States/part-0.parquet")
+                .unwrap(),
+            "s3://bucket/tables/sales/country=United States/part-0.parquet"
+        );
+        assert_eq!(
+            s3.resolve("s3://other/part-0.parquet").unwrap(),
+            "s3://other/part-0.parquet"
+        );
+
+        let abfss = DeltaSharingSigner::new(
+            MockSigner,
+            "abfss://container@account.dfs.core.windows.net/sales",
+        );
+        assert_eq!(
+            abfss.resolve("country=United%20States/part-0.parquet").unwrap(),
+            "abfss://container@account.dfs.core.windows.net/sales/country=United%20States/part-0.parquet"
+        );
+    }
+
+    #[tokio::test]
+    async fn sign_fixture_table() {
+        let signer = DeltaSharingSigner::new(MockSigner, "s3://bucket/delta_table");
+        let mut actions = LogFileAction::parse_commit(0, VERSION_0).unwrap();
+        actions.extend(LogFileAction::parse_commit(1, VERSION_1).unwrap());
+
+        let files = signer
+            .sign_actions(&actions, epoch(1704067200), Duration::from_secs(3600))
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(files.len(), 5);
+        assert_eq!(
+            files[1].url(),
+            format!("s3://bucket/delta_table/country=United States/part-00000-5e6f7a8b-c000.snappy.parquet{QUERY}")
+        );
+        assert_eq!(files[4].size(), Some(512));
+        assert!(files
+            .iter()
+            .all(|f| f.expiration_timestamp() == 1704070800000));
+        // The add and remove action of the same file share the same id.
+        assert_eq!(files[0].id(), files[2].id());
+        assert_ne!(files[0].id(), files[1].id());
+    }
+
+    #[tokio::test]
+    async fn sign_reports_errors_per_action() {
+        let signer = DeltaSharingSigner::new(
+            FailingSigner::new(MockSigner).then_fail(SignerError::throttled("Slow down")),
+            "s3://bucket/delta_table",
+        );
+        let commit = concat!(
+            r#"{"add":{"path":"file:///tmp/part-0.parquet","size":1}}"#,
+            "\n",
+            r#"{"add":{"path":"%FF.parquet","size":1}}"#,
+            "\n",
+            r#"{"add":{"path":"part-1.parquet","size":1}}"#,
+        );
+        let actions = LogFileAction::parse_commit(0, commit).unwrap();
+        let files = signer
+            .sign_actions(&actions, epoch(1704067200), Duration::from_secs(3600))
+            .await;
+        assert_eq!(files.len(), 3);
+        assert_eq!(
+            files[0].as_ref().unwrap_err().kind(),
+            SignerErrorKind::Throttled
+        );
+        assert_eq!(
+            files[1].as_ref().unwrap_err().kind(),
+            SignerErrorKind::CloudUriParseError
+        );
+        assert_eq!(
+            files[2].as_ref().unwrap().url(),
+            format!("s3://bucket/delta_table/part-1.parquet{QUERY}")
+        );
+    }
+
+    /// A signer whose `sign_many` drops the last URL of every batch.
+    #[derive(Debug)]
+    struct DroppingSigner;
+
+    #[async_trait::async_trait]
+    impl CloudFileSigner for DroppingSigner {
+        async fn sign(
+            &self,
+            path: &str,
+            valid_from: SystemTime,
+            expires_in: Duration,
+            permission: Permission,
+        ) -> Result<PresignedUrl, SignerError> {
+            MockSigner
+                .sign(path, valid_from, expires_in, permission)
+                .await
+        }
+
+        async fn sign_many(
+            &self,
+            paths: &[&str],
+            valid_from: SystemTime,
+            expires_in: Duration,
+            permission: Permission,
+        ) -> Vec<Result<PresignedUrl, SignerError>> {
+            let mut results = MockSigner
+                .sign_many(paths, valid_from, expires_in, permission)
+                .await;
+            results.pop();
+            results
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_urls_are_errors() {
+        let signer = DeltaSharingSigner::new(DroppingSigner, "s3://bucket/delta_table");
+        let actions = LogFileAction::parse_commit(0, VERSION_0).unwrap();
+        let files = signer
+            .sign_actions(&actions, epoch(1704067200), Duration::from_secs(3600))
+            .await;
+        assert_eq!(files.len(), 2);
+        assert!(files
+            .iter()
+            .all(|f| f.as_ref().unwrap_err().kind() == SignerErrorKind::Other));
+    }
+
+    #[tokio::test]
+    async fn file_action_json() {
+        let signer = DeltaSharingSigner::new(MockSigner, "s3://bucket/delta_table");
+        let actions = LogFileAction::parse_commit(0, VERSION_0).unwrap();
+        let files = signer
+            .sign_actions(&actions[..1], epoch(1704067200), Duration::from_secs(3600))
+            .await;
+        let json: serde_json::Value =
+            serde_json::from_str(&files[0].as_ref().unwrap().to_json()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "file": {
+                    "url": format!("s3://bucket/delta_table/country=NL/part-00000-1a2b3c4d-c000.snappy.parquet{QUERY}"),
+                    "id": file_id("country=NL/part-00000-1a2b3c4d-c000.snappy.parquet"),
+                    "partitionValues": {"country": "NL"},
+                    "size": 1024,
+                    "stats": "{\"numRecords\":10,\"minValues\":{\"id\":1},\"maxValues\":{\"id\":10},\"nullCount\":{\"id\":0}}",
+                    "version": 0,
+                    "timestamp": 1704067200000i64,
+                    "expirationTimestamp": 1704070800000i64,
+                }
+            })
+        );
+    }
+}
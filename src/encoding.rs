@@ -0,0 +1,171 @@
+//! Percent-encoding rules for object keys.
+//!
+//! The signers in this crate use the following conventions for object keys:
+//!
+//! - Keys in cloud native URIs (`s3://`, `gs://`) are taken verbatim. No
+//!   percent-decoding is applied, so `s3://bucket/100%25` refers to the key
+//!   `100%25`.
+//! - Keys in URLs (`http://`, `https://`) and Hadoop style URIs (`abfs://`,
+//!   `abfss://`) are percent-decoded, so
+//!   `https://bucket.s3.amazonaws.com/Q1%202024` refers to the key `Q1 2024`.
+//! - Keys in signed URLs are percent-encoded. Every byte except the
+//!   unreserved characters (`A-Z`, `a-z`, `0-9`, `-`, `_`, `.`, `~`) and the
+//!   path separator `/` is encoded.
+
+use http::Uri;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use crate::SignerError;
+
+/// Characters that are percent-encoded when an object key is placed in the
+/// path of a URL.
+const KEY_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~')
+    .remove(b'/');
+
+/// Percent-decode a component of a URL path into a UTF-8 string.
+pub(crate) fn decode(s: &str) -> Result<String, SignerError> {
+    percent_decode_str(s)
+        .decode_utf8()
+        .map(|d| d.into_owned())
+        .map_err(|e| {
+            SignerError::uri_parse_error(format!(
+                "Invalid URI: percent-decoded path is not valid UTF-8. Cause: {e}. Received: `{s}`."
+            ))
+        })
+}
+
+/// Return the percent-decoded value of the first query parameter of `uri`
+/// with the given name.
+pub(crate) fn query_param(uri: &Uri, name: &str) -> Result<Option<String>, SignerError> {
+    let Some(query) = uri.query() else {
+        return Ok(None);
+    };
+    query
+        .split('&')
+        .find_map(|pair| match pair.split_once('=') {
+            Some((key, value)) if key == name => Some(value),
+            _ => None,
+        })
+        .map(decode)
+        .transpose()
+}
+
+/// Split a query string into its percent-decoded name and value pairs, in
+/// the order in which they appear. A parameter without `=` has an empty
+/// value.
+pub(crate) fn query_pairs(query: &str) -> Result<Vec<(String, String)>, SignerError> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Ok((decode(name)?, decode(value)?))
+        })
+        .collect()
+}
+
+/// Return the character that appends a query parameter to `url`: `&` if
+/// it already has a query string, `?` otherwise.
+pub(crate) fn query_separator(url: &str) -> char {
+    if url.contains('?') {
+        '&'
+    } else {
+        '?'
+    }
+}
+
+/// Percent-encode an object key so that it can be placed in the path of a
+/// URL.
+pub(crate) fn encode_key(key: &str) -> String {
+    utf8_percent_encode(key, KEY_ENCODE_SET).to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use crate::error::SignerErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn encode_special_characters() {
+        assert_eq!(
+            encode_key("reports/Q1 2024/naïve+data#1.csv"),
+            "reports/Q1%202024/na%C3%AFve%2Bdata%231.csv"
+        );
+    }
+
+    #[test]
+    fn encode_keeps_unreserved_characters() {
+        assert_eq!(encode_key("a-b_c.d~e/f"), "a-b_c.d~e/f");
+    }
+
+    #[test]
+    fn decode_special_characters() {
+        assert_eq!(
+            decode("reports/Q1%202024/na%C3%AFve%2Bdata%231.csv").unwrap(),
+            "reports/Q1 2024/naïve+data#1.csv"
+        );
+    }
+
+    #[test]
+    fn decode_keeps_plus_sign() {
+        assert_eq!(decode("a+b").unwrap(), "a+b");
+    }
+
+    #[test]
+    fn decode_invalid_utf8() {
+        let err = decode("%FF").unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::CloudUriParseError);
+    }
+
+    #[test]
+    fn query_param_lookup() {
+        let uri: Uri = "https://example.com/key?a=1&versionId=abc%2B1&b"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            query_param(&uri, "versionId").unwrap().as_deref(),
+            Some("abc+1")
+        );
+        assert_eq!(query_param(&uri, "b").unwrap(), None);
+        assert_eq!(query_param(&uri, "missing").unwrap(), None);
+    }
+
+    #[test]
+    fn query_pairs_are_decoded_in_order() {
+        assert_eq!(
+            query_pairs("b=2&a=x%2Fy&flag").unwrap(),
+            vec![
+                ("b".to_string(), "2".to_string()),
+                ("a".to_string(), "x/y".to_string()),
+                ("flag".to_string(), String::new()),
+            ]
+        );
+        assert!(query_pairs("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn query_param_without_query() {
+        let uri: Uri = "https://example.com/key".parse().unwrap();
+        assert_eq!(query_param(&uri, "versionId").unwrap(), None);
+    }
+
+    proptest! {
+        #[test]
+        fn encoded_key_round_trips(key in "\\PC*") {
+            prop_assert_eq!(decode(&encode_key(&key)).unwrap(), key);
+        }
+
+        #[test]
+        fn encoded_key_is_a_valid_uri_path(key in "\\PC*") {
+            let uri = format!("https://example.com/{}", encode_key(&key));
+            prop_assert!(uri.parse::<http::Uri>().is_ok());
+        }
+    }
+}
@@ -1,17 +1,36 @@
 //! Errors that can occur while signing a URL.
 
+use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
 /// An error that occurred while signing a URL.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+///
+/// Errors that are caused by an error of a cloud provider's SDK keep that
+/// error as their [`source`](Error::source). Two errors are equal if their
+/// kind and message are equal, regardless of their source.
+#[derive(Debug, Clone)]
 pub struct SignerError {
     kind: SignerErrorKind,
     message: String,
+    source: Option<Arc<dyn Error + Send + Sync>>,
 }
 
 impl SignerError {
     fn new(kind: SignerErrorKind, message: String) -> Self {
-        Self { kind, message }
+        Self {
+            kind,
+            message,
+            source: None,
+        }
+    }
+
+    /// Attach the error that caused this error.
+    #[must_use]
+    pub fn with_source(mut self, source: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        self.source = Some(Arc::from(source.into()));
+        self
     }
 
     /// Return the kind of error.
@@ -26,6 +45,14 @@ impl SignerError {
         &self.message
     }
 
+    /// Return `true` if the operation that failed may succeed when it is
+    /// retried, because the error was caused by throttling or by the
+    /// network.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        self.kind.is_retryable()
+    }
+
     /// Create a new `CloudUriParseError`.
     pub fn uri_parse_error(message: impl Into<String>) -> Self {
         Self::new(SignerErrorKind::CloudUriParseError, message.into())
@@ -36,11 +63,51 @@ impl SignerError {
         Self::new(SignerErrorKind::ExpirationTooLong, message.into())
     }
 
+    /// Create a new `InvalidExpiration` error.
+    pub fn invalid_expiration(message: impl Into<String>) -> Self {
+        Self::new(SignerErrorKind::InvalidExpiration, message.into())
+    }
+
+    /// Create a new `InvalidStartTime` error.
+    pub fn invalid_start_time(message: impl Into<String>) -> Self {
+        Self::new(SignerErrorKind::InvalidStartTime, message.into())
+    }
+
     /// Create a new `PermissionNotSupported` error.
     pub fn permission_not_supported(message: impl Into<String>) -> Self {
         Self::new(SignerErrorKind::PermissionNotSupported, message.into())
     }
 
+    /// Create a new `OptionNotSupported` error.
+    pub fn option_not_supported(message: impl Into<String>) -> Self {
+        Self::new(SignerErrorKind::OptionNotSupported, message.into())
+    }
+
+    /// Create a new `SigningError` error.
+    pub fn signing_error(message: impl Into<String>) -> Self {
+        Self::new(SignerErrorKind::SigningError, message.into())
+    }
+
+    /// Create a new `CredentialsError`.
+    pub fn credentials_error(message: impl Into<String>) -> Self {
+        Self::new(SignerErrorKind::CredentialsError, message.into())
+    }
+
+    /// Create a new `AccountMismatch` error.
+    pub fn account_mismatch(message: impl Into<String>) -> Self {
+        Self::new(SignerErrorKind::AccountMismatch, message.into())
+    }
+
+    /// Create a new `Throttled` error.
+    pub fn throttled(message: impl Into<String>) -> Self {
+        Self::new(SignerErrorKind::Throttled, message.into())
+    }
+
+    /// Create a new `Network` error.
+    pub fn network_error(message: impl Into<String>) -> Self {
+        Self::new(SignerErrorKind::Network, message.into())
+    }
+
     /// Create a new Other error.
     pub fn other_error(message: impl Into<String>) -> Self {
         Self::new(SignerErrorKind::Other, message.into())
@@ -53,31 +120,209 @@ impl Display for SignerError {
     }
 }
 
-impl std::error::Error for SignerError {}
+impl Error for SignerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn Error + 'static))
+    }
+}
+
+impl PartialEq for SignerError {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind && self.message == other.message
+    }
+}
+
+impl Eq for SignerError {}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SignerError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(serde::Serialize)]
+        struct SerializedError<'a> {
+            kind: SignerErrorKind,
+            message: &'a str,
+            retryable: bool,
+        }
+
+        SerializedError {
+            kind: self.kind,
+            message: &self.message,
+            retryable: self.is_retryable(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SignerError {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        struct DeserializedError {
+            kind: SignerErrorKind,
+            message: String,
+        }
+
+        let error = DeserializedError::deserialize(deserializer)?;
+        Ok(Self::new(error.kind, error.message))
+    }
+}
+
+impl Hash for SignerError {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind.hash(state);
+        self.message.hash(state);
+    }
+}
 
 /// The kind of error that occurred while signing a URL.
+///
+/// With the `serde` feature, kinds are represented by their `Display` name,
+/// such as `"CREDENTIALS_ERROR"`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "SCREAMING_SNAKE_CASE"))]
 pub enum SignerErrorKind {
     /// The URI of the object could not be parsed.
     CloudUriParseError,
     /// The requested permission is not supported by the signer.
     PermissionNotSupported,
+    /// The requested sign option is not supported by the signer.
+    OptionNotSupported,
     /// The configured expiration duration is too long.
     ExpirationTooLong,
+    /// The configured expiration duration is zero.
+    InvalidExpiration,
+    /// The configured start time is too far in the future, or the validity
+    /// window has already ended.
+    InvalidStartTime,
     /// An error occured during the signature calculation.
     SigningError,
+    /// The credentials of the signer are missing, invalid or expired.
+    CredentialsError,
+    /// The URI refers to a different account than the one the signer is
+    /// configured for.
+    AccountMismatch,
+    /// The cloud provider rejected the request because too many requests
+    /// were made.
+    Throttled,
+    /// The cloud provider could not be reached.
+    #[cfg_attr(feature = "serde", serde(rename = "NETWORK_ERROR"))]
+    Network,
     /// Some other error occurred.
+    #[cfg_attr(feature = "serde", serde(rename = "OTHER_ERROR"))]
     Other,
 }
 
+impl SignerErrorKind {
+    /// Return `true` if errors of this kind are transient, so the operation
+    /// that failed may succeed when it is retried.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, SignerErrorKind::Throttled | SignerErrorKind::Network)
+    }
+}
+
 impl Display for SignerErrorKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             SignerErrorKind::CloudUriParseError => write!(f, "CLOUD_URI_PARSE_ERROR"),
             SignerErrorKind::PermissionNotSupported => write!(f, "PERMISSION_NOT_SUPPORTED"),
+            SignerErrorKind::OptionNotSupported => write!(f, "OPTION_NOT_SUPPORTED"),
             SignerErrorKind::ExpirationTooLong => write!(f, "EXPIRATION_TOO_LONG"),
+            SignerErrorKind::InvalidExpiration => write!(f, "INVALID_EXPIRATION"),
+            SignerErrorKind::InvalidStartTime => write!(f, "INVALID_START_TIME"),
             SignerErrorKind::SigningError => write!(f, "SIGNING_ERROR"),
+            SignerErrorKind::CredentialsError => write!(f, "CREDENTIALS_ERROR"),
+            SignerErrorKind::AccountMismatch => write!(f, "ACCOUNT_MISMATCH"),
+            SignerErrorKind::Throttled => write!(f, "THROTTLED"),
+            SignerErrorKind::Network => write!(f, "NETWORK_ERROR"),
             SignerErrorKind::Other => write!(f, "OTHER_ERROR"),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::hash_map::DefaultHasher;
+    use std::io;
+
+    use super::*;
+
+    fn hash(error: &SignerError) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        error.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn source_is_kept() {
+        let error = SignerError::network_error("Connection failed")
+            .with_source(io::Error::new(io::ErrorKind::ConnectionReset, "reset"));
+        let source = error.source().unwrap();
+        assert_eq!(source.to_string(), "reset");
+        assert!(source.downcast_ref::<io::Error>().is_some());
+
+        let cloned = error.clone();
+        assert!(cloned.source().is_some());
+    }
+
+    #[test]
+    fn equality_ignores_source() {
+        let with_source = SignerError::throttled("Slow down")
+            .with_source(io::Error::new(io::ErrorKind::TimedOut, "503"));
+        let without_source = SignerError::throttled("Slow down");
+        assert_eq!(with_source, without_source);
+        assert_eq!(hash(&with_source), hash(&without_source));
+        assert_ne!(with_source, SignerError::network_error("Slow down"));
+    }
+
+    #[test]
+    fn retryable_kinds() {
+        assert!(SignerError::throttled("").is_retryable());
+        assert!(SignerError::network_error("").is_retryable());
+        assert!(!SignerError::credentials_error("").is_retryable());
+        assert!(!SignerError::account_mismatch("").is_retryable());
+        assert!(!SignerError::expiration_too_long("").is_retryable());
+        assert!(!SignerError::other_error("").is_retryable());
+    }
+
+    #[test]
+    fn display() {
+        let error = SignerError::account_mismatch("wrong account");
+        assert_eq!(error.to_string(), "[ACCOUNT_MISMATCH] wrong account");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trips() {
+        let error = SignerError::network_error("connection reset")
+            .with_source(io::Error::new(io::ErrorKind::Other, "reset"));
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "kind": "NETWORK_ERROR",
+                "message": "connection reset",
+                "retryable": true,
+            })
+        );
+        let parsed = serde_json::from_value::<SignerError>(json).unwrap();
+        assert_eq!(parsed, error);
+        assert!(parsed.source().is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_kind_matches_display() {
+        for kind in [
+            SignerErrorKind::CloudUriParseError,
+            SignerErrorKind::InvalidStartTime,
+            SignerErrorKind::Network,
+            SignerErrorKind::Other,
+        ] {
+            assert_eq!(serde_json::to_string(&kind).unwrap(), format!("\"{kind}\""));
+        }
+    }
+}
@@ -0,0 +1,368 @@
+//! An implementation of the [`CloudFileSigner`] trait for Google Cloud CDN.
+
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use base64::engine::general_purpose::URL_SAFE;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use crate::clock::{Clock, SystemClock};
+use crate::encoding::{encode_key, query_separator};
+use crate::telemetry::instrument_sign;
+use crate::time::epoch_seconds;
+use crate::{CloudFileSigner, Permission, PresignedUrl, Provider, SignerError};
+
+use super::uri::GcpUri;
+
+/// The name of the cookie that carries a Cloud CDN signed cookie.
+pub const CLOUD_CDN_COOKIE_NAME: &str = "Cloud-CDN-Cookie";
+
+/// A signer for content served through Google Cloud CDN.
+///
+/// Cloud CDN signed URLs and signed cookies are signed offline with a named
+/// HMAC-SHA1 key registered with the backend. Paths are resolved against the
+/// domain of the CDN:
+///
+/// - `https://` URLs are signed as is.
+/// - `gs://` URIs are served from the CDN using the object key as the path.
+///   The bucket must be the backend bucket of the CDN.
+/// - Other paths are appended to the CDN domain.
+///
+/// Cloud CDN signatures carry no start time, so signed URLs are valid from
+/// the moment they are issued, and [`CloudFileSigner::sign`] rejects a
+/// `valid_from` in the future.
+///
+/// # Example
+/// ```rust
+/// # use cloud_file_signer::SignerError;
+/// # fn main() -> Result<(), SignerError> {
+/// use std::time::{Duration, SystemTime};
+/// use cloud_file_signer::gcp::CloudCdnSigner;
+///
+/// let signer = CloudCdnSigner::from_base64_key(
+///     "my-key",
+///     "https://cdn.example.com",
+///     "nZtRohdNF9m3cKM24IcK4w==",
+/// )?;
+///
+/// let expires = SystemTime::now() + Duration::from_secs(3600);
+/// let url = signer.sign_url("https://cdn.example.com/images/cat.jpg", expires)?;
+/// let cookie = signer.signed_cookie("https://cdn.example.com/images/", expires)?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone)]
+pub struct CloudCdnSigner {
+    key_name: String,
+    key: Vec<u8>,
+    domain: String,
+    clock: Arc<dyn Clock>,
+}
+
+impl CloudCdnSigner {
+    /// Create a new signer for the CDN served from `domain`, for example
+    /// `https://cdn.example.com`, using the raw bytes of the signing key.
+    pub fn new(
+        key_name: impl Into<String>,
+        domain: impl Into<String>,
+        key: impl Into<Vec<u8>>,
+    ) -> Self {
+        Self {
+            key_name: key_name.into(),
+            key: key.into(),
+            domain: domain.into().trim_end_matches('/').to_string(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Create a new signer from a base64url encoded signing key, the format
+    /// in which Cloud CDN issues keys.
+    pub fn from_base64_key(
+        key_name: impl Into<String>,
+        domain: impl Into<String>,
+        key: &str,
+    ) -> Result<Self, SignerError> {
+        let key = URL_SAFE.decode(key.trim()).map_err(|e| {
+            SignerError::signing_error(format!(
+                "Could not decode the Cloud CDN signing key. Cause: {e}"
+            ))
+        })?;
+        Ok(Self::new(key_name, domain, key))
+    }
+
+    /// Read the current time from `clock` instead of the system time.
+    #[must_use]
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Return the name of the key used for signing.
+    #[must_use]
+    pub fn key_name(&self) -> &str {
+        &self.key_name
+    }
+
+    /// Return the domain of the CDN.
+    #[must_use]
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Sign a single URL. The URL is valid until `expires`.
+    pub fn sign_url(&self, url: &str, expires: SystemTime) -> Result<String, SignerError> {
+        let url_to_sign = format!(
+            "{url}{}Expires={}&KeyName={}",
+            query_separator(url),
+            epoch_seconds(expires)?,
+            self.key_name
+        );
+        let signature = self.hmac_signature(&url_to_sign)?;
+        Ok(format!("{url_to_sign}&Signature={signature}"))
+    }
+
+    /// Sign `url` with a signature that is valid for every URL starting with
+    /// `url_prefix`. The query string of the signed URL can be reused for
+    /// other URLs that share the prefix.
+    pub fn sign_url_prefix(
+        &self,
+        url: &str,
+        url_prefix: &str,
+        expires: SystemTime,
+    ) -> Result<String, SignerError> {
+        let policy = format!(
+            "URLPrefix={}&Expires={}&KeyName={}",
+            URL_SAFE.encode(url_prefix),
+            epoch_seconds(expires)?,
+            self.key_name
+        );
+        let signature = self.hmac_signature(&policy)?;
+        Ok(format!(
+            "{url}{}{policy}&Signature={signature}",
+            query_separator(url)
+        ))
+    }
+
+    /// Create the value of a signed cookie that grants access to every URL
+    /// starting with `url_prefix`. The value must be set on the
+    /// [`CLOUD_CDN_COOKIE_NAME`] cookie.
+    pub fn signed_cookie(
+        &self,
+        url_prefix: &str,
+        expires: SystemTime,
+    ) -> Result<String, SignerError> {
+        let policy = format!(
+            "URLPrefix={}:Expires={}:KeyName={}",
+            URL_SAFE.encode(url_prefix),
+            epoch_seconds(expires)?,
+            self.key_name
+        );
+        let signature = self.hmac_signature(&policy)?;
+        Ok(format!("{policy}:Signature={signature}"))
+    }
+
+    fn hmac_signature(&self, value: &str) -> Result<String, SignerError> {
+        let mut mac = Hmac::<Sha1>::new_from_slice(&self.key).map_err(|e| {
+            SignerError::signing_error(format!("Invalid Cloud CDN signing key. Cause: {e}"))
+        })?;
+        mac.update(value.as_bytes());
+        Ok(URL_SAFE.encode(mac.finalize().into_bytes()))
+    }
+
+    fn resolve_url(&self, path: &str) -> Result<String, SignerError> {
+        if path.starts_with("https://") || path.starts_with("http://") {
+            return Ok(path.to_string());
+        }
+        if let Some(("gs", _)) = path.split_once("://") {
+            let gcp_uri = path.parse::<GcpUri>()?;
+            return Ok(format!("{}/{}", self.domain, encode_key(gcp_uri.key())));
+        }
+        if path.contains("://") {
+            return Err(SignerError::uri_parse_error(format!(
+                "Unsupported URI scheme. Cloud CDN paths should be `https` URLs, `gs` URIs or paths relative to the CDN. Received URI: `{path}`."
+            )));
+        }
+        Ok(format!("{}/{}", self.domain, path.trim_start_matches('/')))
+    }
+}
+
+impl Debug for CloudCdnSigner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CloudCdnSigner")
+            .field("key_name", &self.key_name)
+            .field("domain", &self.domain)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait::async_trait]
+impl CloudFileSigner for CloudCdnSigner {
+    async fn sign(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expiration: Duration,
+        permission: Permission,
+    ) -> Result<PresignedUrl, SignerError> {
+        instrument_sign(Provider::CloudCdn, permission, expiration, async {
+            if permission != Permission::Read {
+                return Err(SignerError::permission_not_supported(
+                    "Cloud CDN signed URLs only support read permissions",
+                ));
+            }
+
+            if valid_from > self.clock.now() {
+                return Err(SignerError::invalid_start_time(
+                    "Cloud CDN signed URLs are valid from the moment they are issued, so they cannot start in the future.",
+                ));
+            }
+            let expires = valid_from.checked_add(expiration).ok_or_else(|| {
+                SignerError::invalid_expiration(format!(
+                    "Invalid expiration of {} seconds: the expiry of the Cloud CDN signed URL is out of range.",
+                    expiration.as_secs()
+                ))
+            })?;
+
+            let url = self.resolve_url(path)?;
+            let signed_url = self.sign_url(&url, expires)?;
+            Ok(PresignedUrl::new(signed_url, valid_from, expiration)
+                .with_provider(Provider::CloudCdn)
+                .with_permission(Permission::Read))
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::UNIX_EPOCH;
+
+    use crate::error::SignerErrorKind;
+    use crate::ManualClock;
+
+    use super::*;
+
+    const KEY: &str = "nZtRohdNF9m3cKM24IcK4w==";
+
+    fn signer() -> CloudCdnSigner {
+        CloudCdnSigner::from_base64_key("my-key", "https://cdn.example.com/", KEY).unwrap()
+    }
+
+    fn epoch(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn signed_url() {
+        let url = signer()
+            .sign_url("https://cdn.example.com/images/cat.jpg", epoch(1704067200))
+            .unwrap();
+        assert_eq!(
+            url,
+            "https://cdn.example.com/images/cat.jpg?Expires=1704067200&KeyName=my-key&Signature=W1vbAgYzpnwojiHP_4_LEa2CrUw="
+        );
+    }
+
+    #[test]
+    fn signed_url_with_existing_query() {
+        let url = signer()
+            .sign_url(
+                "https://cdn.example.com/images/cat.jpg?size=large",
+                epoch(1704067200),
+            )
+            .unwrap();
+        assert_eq!(
+            url,
+            "https://cdn.example.com/images/cat.jpg?size=large&Expires=1704067200&KeyName=my-key&Signature=9lrnkRCXIVnKw4yepkyr3N0zBKQ="
+        );
+    }
+
+    #[test]
+    fn signed_url_prefix() {
+        let url = signer()
+            .sign_url_prefix(
+                "https://cdn.example.com/images/cat.jpg",
+                "https://cdn.example.com/images/",
+                epoch(1704067200),
+            )
+            .unwrap();
+        assert_eq!(
+            url,
+            "https://cdn.example.com/images/cat.jpg?URLPrefix=aHR0cHM6Ly9jZG4uZXhhbXBsZS5jb20vaW1hZ2VzLw==&Expires=1704067200&KeyName=my-key&Signature=eB653QqabtO11sosrunO9ov1ejg="
+        );
+    }
+
+    #[test]
+    fn signed_cookie() {
+        let cookie = signer()
+            .signed_cookie("https://cdn.example.com/images/", epoch(1704067200))
+            .unwrap();
+        assert_eq!(
+            cookie,
+            "URLPrefix=aHR0cHM6Ly9jZG4uZXhhbXBsZS5jb20vaW1hZ2VzLw==:Expires=1704067200:KeyName=my-key:Signature=vYKqd2WUkOyBamVEukhHhwgUZjY="
+        );
+    }
+
+    #[test]
+    fn debug_hides_key() {
+        let debug = format!("{:?}", signer());
+        assert!(!debug.contains(KEY));
+        assert!(debug.contains("my-key"));
+    }
+
+    #[test]
+    fn invalid_key() {
+        let err =
+            CloudCdnSigner::from_base64_key("my-key", "https://cdn.example.com", "not base64!")
+                .unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::SigningError);
+    }
+
+    #[tokio::test]
+    async fn sign_gs_uri() {
+        let presigned_url = signer()
+            .sign(
+                "gs://bucket/reports/Q1 2024.csv",
+                epoch(1704063600),
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            presigned_url.expose_url(),
+            "https://cdn.example.com/reports/Q1%202024.csv?Expires=1704067200&KeyName=my-key&Signature=74CKxHXrDfAjjP28KYbvh7HMa64="
+        );
+    }
+
+    #[tokio::test]
+    async fn future_start_is_rejected() {
+        let err = signer()
+            .with_clock(ManualClock::new(epoch(1704063600)))
+            .sign(
+                "images/cat.jpg",
+                epoch(1704063601),
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::InvalidStartTime);
+    }
+
+    #[tokio::test]
+    async fn sign_write_not_supported() {
+        let err = signer()
+            .sign(
+                "images/cat.jpg",
+                SystemTime::now(),
+                Duration::from_secs(3600),
+                Permission::Write,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::PermissionNotSupported);
+    }
+}
@@ -0,0 +1,369 @@
+//! An implementation of the [`CredentialVendor`] trait for Google Cloud
+//! Storage.
+
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use google_cloud_storage::client::ClientConfig;
+use google_cloud_token::TokenSource;
+use serde_json::{json, Value};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::clock::{Clock, SystemClock};
+use crate::credentials::{directory_prefix, CredentialVendor, GcpCredentials, ScopedCredentials};
+use crate::{Permission, SignerError};
+
+use super::uri::GcpUri;
+
+/// The endpoint of the Security Token Service that downscopes tokens.
+const STS_TOKEN_ENDPOINT: &str = "https://sts.googleapis.com/v1/token";
+const TOKEN_EXCHANGE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+/// The latest timestamp a Credential Access Boundary condition can express,
+/// `9999-12-31T23:59:59Z`.
+const MAX_BOUNDARY_EXPIRY: Duration = Duration::from_secs(253_402_300_799);
+
+/// A vendor of prefix-scoped access tokens for Google Cloud Storage.
+///
+/// The access token of the vendor's own credentials is exchanged for a
+/// downscoped token with a Credential Access Boundary that only allows
+/// access to objects below the requested prefix. The effective permissions
+/// are the intersection of the source credentials' permissions and the
+/// boundary.
+///
+/// A downscoped token expires together with the source token, so the
+/// boundary itself only allows access until the requested expiry. The
+/// returned credentials expire at the requested time, or earlier if the
+/// token expires first.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> Result<(), cloud_file_signer::SignerError> {
+/// use std::time::Duration;
+/// use cloud_file_signer::Permission;
+/// use cloud_file_signer::credentials::CredentialVendor;
+/// use cloud_file_signer::gcp::GcpCredentialVendor;
+///
+/// let vendor = GcpCredentialVendor::from_env().await?;
+/// let credentials = vendor
+///     .vend("gs://bucket/path/to/table/", Duration::from_secs(3600), Permission::Read)
+///     .await?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone)]
+pub struct GcpCredentialVendor {
+    token_source: Arc<dyn TokenSource>,
+    http: reqwest::Client,
+    project_id: Option<String>,
+    clock: Arc<dyn Clock>,
+}
+
+impl GcpCredentialVendor {
+    /// Create a new vendor that downscopes tokens from `token_source`.
+    pub fn new(token_source: Arc<dyn TokenSource>) -> Self {
+        Self {
+            token_source,
+            http: reqwest::Client::new(),
+            project_id: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Create a new vendor using the application default credentials.
+    pub async fn from_env() -> Result<Self, SignerError> {
+        let client_config = ClientConfig::default()
+            .with_auth()
+            .await
+            .map_err(|e| SignerError::credentials_error(e.to_string()).with_source(e))?;
+        let token_source = match client_config.token_source_provider {
+            Some(provider) => provider.token_source(),
+            None => {
+                return Err(SignerError::credentials_error(
+                    "No Google Cloud credentials found to downscope.",
+                ))
+            }
+        };
+        let mut vendor = Self::new(token_source);
+        vendor.project_id = client_config.project_id;
+        Ok(vendor)
+    }
+
+    /// Set the project that is handed out together with the tokens.
+    #[must_use]
+    pub fn with_project_id(mut self, project_id: impl Into<String>) -> Self {
+        self.project_id = Some(project_id.into());
+        self
+    }
+
+    /// Read the current time from `clock` instead of the system time.
+    #[must_use]
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    async fn exchange_token(
+        &self,
+        access_boundary: &Value,
+    ) -> Result<(String, Option<Duration>), SignerError> {
+        let source_token = self
+            .token_source
+            .token()
+            .await
+            .map_err(|e| SignerError::credentials_error(e.to_string()).with_source(e))?;
+        let source_token = source_token
+            .strip_prefix("Bearer ")
+            .unwrap_or(&source_token);
+
+        let options = json!({ "accessBoundary": access_boundary }).to_string();
+        let form = [
+            ("grant_type", TOKEN_EXCHANGE_GRANT_TYPE),
+            ("subject_token_type", ACCESS_TOKEN_TYPE),
+            ("requested_token_type", ACCESS_TOKEN_TYPE),
+            ("subject_token", source_token),
+            ("options", &options),
+        ];
+        let response = self
+            .http
+            .post(STS_TOKEN_ENDPOINT)
+            .form(&form)
+            .send()
+            .await
+            .map_err(request_error)?;
+        let status = response.status();
+        let body = response.text().await.map_err(request_error)?;
+        if !status.is_success() {
+            return Err(status_error(status.as_u16(), &body));
+        }
+        parse_token_response(&body)
+    }
+}
+
+impl Debug for GcpCredentialVendor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GcpCredentialVendor")
+            .field("project_id", &self.project_id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialVendor for GcpCredentialVendor {
+    async fn vend(
+        &self,
+        prefix: &str,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Result<ScopedCredentials, SignerError> {
+        let uri = prefix.parse::<GcpUri>()?;
+        let requested_at = self.clock.now();
+        let expires_at = requested_at
+            .checked_add(expires_in)
+            .filter(|expires_at| *expires_at <= UNIX_EPOCH + MAX_BOUNDARY_EXPIRY)
+            .ok_or_else(|| {
+                SignerError::invalid_expiration(format!(
+                    "Downscoped Google Cloud Storage credentials cannot expire after 9999-12-31T23:59:59Z. Received: {} seconds.",
+                    expires_in.as_secs()
+                ))
+            })?;
+        let access_boundary = access_boundary(uri.bucket(), uri.key(), permission, expires_at);
+        let (access_token, lifetime) = self.exchange_token(&access_boundary).await?;
+
+        let expires_at = match lifetime {
+            Some(lifetime) => requested_at
+                .checked_add(lifetime)
+                .map_or(expires_at, |token_expires_at| {
+                    expires_at.min(token_expires_at)
+                }),
+            None => expires_at,
+        };
+        let mut credentials = GcpCredentials::new(access_token).with_expires_at(expires_at);
+        if let Some(project_id) = &self.project_id {
+            credentials = credentials.with_project_id(project_id);
+        }
+        Ok(credentials.into())
+    }
+}
+
+/// Build a Credential Access Boundary that grants `permission` on every
+/// object below the directory `prefix` in `bucket`, and allows listing
+/// those objects, until `expires_at`.
+fn access_boundary(
+    bucket: &str,
+    prefix: &str,
+    permission: Permission,
+    expires_at: SystemTime,
+) -> Value {
+    let roles = match permission {
+        Permission::Read => vec!["inRole:roles/storage.objectViewer"],
+        Permission::Write => vec![
+            "inRole:roles/storage.objectCreator",
+            "inRole:roles/storage.objectViewer",
+        ],
+    };
+    let prefix = directory_prefix(prefix)
+        .replace('\\', "\\\\")
+        .replace('\'', "\\'");
+    let expires_at = DateTime::<Utc>::from(expires_at).to_rfc3339_opts(SecondsFormat::Secs, true);
+    let expression = format!(
+        "request.time < timestamp('{expires_at}') && \
+         (resource.name.startsWith('projects/_/buckets/{bucket}/objects/{prefix}') || \
+         api.getAttribute('storage.googleapis.com/objectListPrefix', '').startsWith('{prefix}'))"
+    );
+    json!({
+        "accessBoundaryRules": [
+            {
+                "availableResource": format!("//storage.googleapis.com/projects/_/buckets/{bucket}"),
+                "availablePermissions": roles,
+                "availabilityCondition": {
+                    "title": "cloud-file-signer prefix",
+                    "expression": expression,
+                },
+            },
+        ],
+    })
+}
+
+fn request_error(e: reqwest::Error) -> SignerError {
+    let message = format!("Token exchange request failed. Cause: {e}");
+    if e.is_timeout() || e.is_connect() || e.is_request() || e.is_body() {
+        SignerError::network_error(message).with_source(e)
+    } else {
+        SignerError::other_error(message).with_source(e)
+    }
+}
+
+fn status_error(status: u16, body: &str) -> SignerError {
+    let message = format!("Google STS rejected the token exchange with status {status}: {body}");
+    match status {
+        429 | 503 => SignerError::throttled(message),
+        401 | 403 => SignerError::credentials_error(message),
+        _ => SignerError::other_error(message),
+    }
+}
+
+/// Parse the access token and its lifetime from a token exchange response.
+fn parse_token_response(body: &str) -> Result<(String, Option<Duration>), SignerError> {
+    let response: Value = serde_json::from_str(body).map_err(|e| {
+        SignerError::other_error(format!("Invalid token exchange response. Cause: {e}"))
+    })?;
+    let access_token = response["access_token"].as_str().ok_or_else(|| {
+        SignerError::other_error("Token exchange response does not contain an access token")
+    })?;
+    let lifetime = response["expires_in"].as_u64().map(Duration::from_secs);
+    Ok((access_token.to_string(), lifetime))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ManualClock, SignerErrorKind};
+
+    use super::*;
+
+    /// A token source for tests that never reach the token exchange.
+    #[derive(Debug)]
+    struct UnusedTokenSource;
+
+    #[async_trait::async_trait]
+    impl TokenSource for UnusedTokenSource {
+        async fn token(&self) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+            Err("no token".into())
+        }
+    }
+
+    fn expires_at() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1704067200)
+    }
+
+    #[test]
+    fn read_boundary() {
+        let boundary = access_boundary("bucket", "tables/sales/", Permission::Read, expires_at());
+        let rule = &boundary["accessBoundaryRules"][0];
+        assert_eq!(
+            rule["availableResource"],
+            "//storage.googleapis.com/projects/_/buckets/bucket"
+        );
+        assert_eq!(
+            rule["availablePermissions"],
+            json!(["inRole:roles/storage.objectViewer"])
+        );
+        let expression = rule["availabilityCondition"]["expression"]
+            .as_str()
+            .unwrap();
+        assert_eq!(
+            expression,
+            "request.time < timestamp('2024-01-01T00:00:00Z') && \
+             (resource.name.startsWith('projects/_/buckets/bucket/objects/tables/sales/') || \
+             api.getAttribute('storage.googleapis.com/objectListPrefix', '').startsWith('tables/sales/'))"
+        );
+    }
+
+    #[test]
+    fn boundary_is_scoped_to_directory() {
+        let boundary = access_boundary("bucket", "tables/sales", Permission::Read, expires_at());
+        let expression = boundary["accessBoundaryRules"][0]["availabilityCondition"]["expression"]
+            .as_str()
+            .unwrap();
+        assert!(expression.contains("objects/tables/sales/')"));
+        assert!(expression.contains(".startsWith('tables/sales/')"));
+    }
+
+    #[test]
+    fn boundary_escapes_quotes() {
+        let boundary = access_boundary("bucket", "it's/", Permission::Write, expires_at());
+        let expression = boundary["accessBoundaryRules"][0]["availabilityCondition"]["expression"]
+            .as_str()
+            .unwrap();
+        assert!(expression.contains("objects/it\\'s/'"));
+    }
+
+    #[tokio::test]
+    async fn out_of_range_expiry_is_an_error() {
+        let vendor = GcpCredentialVendor::new(Arc::new(UnusedTokenSource));
+        let err = vendor
+            .vend("gs://bucket/tables/", Duration::MAX, Permission::Read)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::InvalidExpiration);
+
+        // The expiry is computed from the vendor's clock.
+        let vendor = vendor.with_clock(ManualClock::new(UNIX_EPOCH + MAX_BOUNDARY_EXPIRY));
+        let err = vendor
+            .vend(
+                "gs://bucket/tables/",
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::InvalidExpiration);
+    }
+
+    #[test]
+    fn classify_status() {
+        assert_eq!(
+            status_error(429, "").kind(),
+            crate::SignerErrorKind::Throttled
+        );
+        assert_eq!(
+            status_error(401, "").kind(),
+            crate::SignerErrorKind::CredentialsError
+        );
+        assert_eq!(status_error(400, "").kind(), crate::SignerErrorKind::Other);
+    }
+
+    #[test]
+    fn token_response() {
+        let (token, lifetime) = parse_token_response(
+            r#"{"access_token":"ya29.downscoped","issued_token_type":"urn:ietf:params:oauth:token-type:access_token","token_type":"Bearer","expires_in":3599}"#,
+        )
+        .unwrap();
+        assert_eq!(token, "ya29.downscoped");
+        assert_eq!(lifetime, Some(Duration::from_secs(3599)));
+
+        assert!(parse_token_response(r#"{"error":"invalid_grant"}"#).is_err());
+        assert!(parse_token_response("not json").is_err());
+    }
+}
@@ -1,70 +1,227 @@
-//! An implementation of the [`CloudFileSigner`] trait for Google Cloud Storage.
+//! Implementations of the [`CloudFileSigner`] trait for Google Cloud Storage
+//! and Google Cloud CDN, and of the
+//! [`CredentialVendor`](crate::credentials::CredentialVendor) trait for
+//! Google Cloud Storage.
 
-use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 use std::time::SystemTime;
 
-use chrono::DateTime;
-use chrono::SecondsFormat;
-use chrono::Utc;
 use google_cloud_storage::client::Client;
 use google_cloud_storage::client::ClientConfig;
+use google_cloud_storage::http;
+use google_cloud_storage::sign::SignBy;
+use google_cloud_storage::sign::SignedURLError;
 use google_cloud_storage::sign::SignedURLMethod;
 use google_cloud_storage::sign::SignedURLOptions;
 
+use crate::clock::{Clock, SystemClock};
+use crate::encoding::encode_key;
+use crate::telemetry::{instrument_sign, record_bucket};
+use crate::validation::{SigningWindow, ONE_WEEK};
 use crate::CloudFileSigner;
 use crate::Permission;
 use crate::PresignedUrl;
+use crate::Provider;
+use crate::SignOptions;
 use crate::SignerError;
+use crate::ValidityLimits;
 
 use self::uri::GcpUri;
+use self::v4::ServiceAccountKey;
 
-mod uri;
+mod cdn;
+mod downscope;
+pub(crate) mod uri;
+mod v4;
+mod verify;
+
+pub use cdn::{CloudCdnSigner, CLOUD_CDN_COOKIE_NAME};
+pub use downscope::GcpCredentialVendor;
+pub use verify::GcsHmacVerifier;
+
+/// The limits on the validity window of Google Cloud Storage signed URLs. A
+/// V4 signed URL is valid for at most one week from its `X-Goog-Date`, and
+/// the client dates URLs when they are signed, so they cannot start in the
+/// future.
+pub const GCS_VALIDITY_LIMITS: ValidityLimits =
+    ValidityLimits::new("Google Cloud Storage", ONE_WEEK, Duration::ZERO);
+
+/// The default maximum number of URLs that [`GcpFileSigner`] signs
+/// concurrently in [`CloudFileSigner::sign_many`]. Signing with the IAM
+/// `signBlob` API makes a remote call per URL, so the limit is lower than
+/// [`SIGN_MANY_CONCURRENCY`](crate::SIGN_MANY_CONCURRENCY).
+pub const GCS_SIGN_MANY_CONCURRENCY: usize = 8;
 
 /// A signer for Google Cloud Storage.
+///
+/// A signer created with [`from_client_config`](Self::from_client_config)
+/// or [`from_env`](Self::from_env) from the credentials of a service
+/// account with a private key signs URLs locally. Its signatures are dated
+/// with the start of the validity window, so they honour the
+/// [`Clock`] and the clock skew of the signer, and identical requests yield
+/// identical URLs. Wrapped in a [`TimeBucketedSigner`](crate::TimeBucketedSigner),
+/// such a signer produces the same URL for every request within a bucket.
+///
+/// Otherwise URLs are signed by the Google Cloud Storage client, for
+/// example with the IAM `signBlob` API on Compute Engine. The client dates
+/// every signature with the system time when it is signed, so such signers
+/// do not support [`with_clock`](Self::with_clock) or
+/// [`with_clock_skew`](Self::with_clock_skew), and their URLs differ on
+/// every call, even behind a `TimeBucketedSigner`.
 pub struct GcpFileSigner {
-    client: Client,
+    url_signer: UrlSigner,
+    billing_project: Option<String>,
+    clock: Arc<dyn Clock>,
+    system_clock: bool,
+    clock_skew: Duration,
+    sign_many_concurrency: usize,
+}
+
+/// How a [`GcpFileSigner`] signs URLs.
+enum UrlSigner {
+    /// Sign locally with the private key of a service account.
+    ServiceAccountKey(Box<ServiceAccountKey>),
+    /// Sign with the client, which dates signatures with the system time.
+    Client(Client),
 }
 
 impl GcpFileSigner {
-    /// Create a new signer for Google Cloud Storage.
+    /// Create a new signer for Google Cloud Storage that signs URLs with
+    /// `client`.
     #[must_use]
     pub fn new(client: Client) -> Self {
-        Self { client }
+        Self::with_url_signer(UrlSigner::Client(client))
+    }
+
+    /// Create a new signer for Google Cloud Storage from a client
+    /// configuration. If the configuration holds the private key of a
+    /// service account, URLs are signed locally. Otherwise they are signed
+    /// by a client created from the configuration.
+    #[must_use]
+    pub fn from_client_config(config: ClientConfig) -> Self {
+        if let (Some(client_email), Some(SignBy::PrivateKey(private_key))) =
+            (&config.default_google_access_id, &config.default_sign_by)
+        {
+            if let Ok(key) = ServiceAccountKey::from_pem(client_email, private_key) {
+                return Self::with_url_signer(UrlSigner::ServiceAccountKey(Box::new(key)));
+            }
+        }
+        Self::new(Client::new(config))
     }
 
     /// Create a new signer for Google Cloud Storage using environment variables.
     pub async fn from_env() -> Self {
         let client_config = ClientConfig::default().with_auth().await.unwrap();
-        let client = Client::new(client_config);
-        Self { client }
+        Self::from_client_config(client_config)
+    }
+
+    fn with_url_signer(url_signer: UrlSigner) -> Self {
+        Self {
+            url_signer,
+            billing_project: None,
+            clock: Arc::new(SystemClock),
+            system_clock: true,
+            clock_skew: Duration::ZERO,
+            sign_many_concurrency: GCS_SIGN_MANY_CONCURRENCY,
+        }
     }
 
+    /// Bill requests made with the signed URLs to the given project. This
+    /// is required to sign URLs for objects in requester pays buckets.
+    ///
+    /// The project is added to the signed URLs as the `userProject` query
+    /// parameter.
+    #[must_use]
+    pub fn with_billing_project(mut self, project_id: impl Into<String>) -> Self {
+        self.billing_project = Some(project_id.into());
+        self
+    }
+
+    /// Backdate the `X-Goog-Date` of signed URLs by up to `clock_skew`, so
+    /// they are accepted by clients whose clocks are behind. The reported
+    /// [`PresignedUrl::valid_from`] and the end of the validity window are
+    /// not affected.
+    ///
+    /// Only supported when URLs are signed with a service account key.
+    #[must_use]
+    pub fn with_clock_skew(mut self, clock_skew: Duration) -> Self {
+        self.clock_skew = clock_skew;
+        self
+    }
+
+    /// Sign at most `concurrency` URLs concurrently in
+    /// [`CloudFileSigner::sign_many`]. Defaults to [`GCS_SIGN_MANY_CONCURRENCY`].
+    #[must_use]
+    pub fn with_sign_many_concurrency(mut self, concurrency: usize) -> Self {
+        self.sign_many_concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Read the current time from `clock` instead of the system time.
+    ///
+    /// Only supported when URLs are signed with a service account key.
+    #[must_use]
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self.system_clock = false;
+        self
+    }
+
+    /// Sign a URL for the validity window `window`.
     async fn sign_read_request(
         &self,
         uri: &GcpUri,
-        valid_from: SystemTime,
-        expiration: Duration,
+        window: &SigningWindow,
+        generation: Option<&str>,
     ) -> Result<PresignedUrl, SignerError> {
-        let mut query_params = HashMap::new();
-        query_params.insert(
-            String::from("X-Goog-Date"),
-            vec![DateTime::<Utc>::from(valid_from).to_rfc3339_opts(SecondsFormat::Secs, true)],
-        );
+        let mut query_params = Vec::new();
+        if let Some(project_id) = &self.billing_project {
+            query_params.push(("userProject", project_id.as_str()));
+        }
+        if let Some(generation) = generation {
+            query_params.push(("generation", generation));
+        }
 
+        let client = match &self.url_signer {
+            UrlSigner::ServiceAccountKey(key) => {
+                let signed_url = key.sign_get(uri, &query_params, window);
+                return Ok(
+                    PresignedUrl::new(signed_url, window.valid_from, window.expires_in)
+                        .with_provider(Provider::Gcs)
+                        .with_permission(Permission::Read)
+                        .with_object_uri(format!("gs://{}/{}", uri.bucket(), uri.key())),
+                );
+            }
+            UrlSigner::Client(client) => client,
+        };
+        if !self.system_clock || !self.clock_skew.is_zero() {
+            return Err(SignerError::option_not_supported(
+                "The Google Cloud Storage client dates signed URLs with the system time. A custom clock or clock skew requires signing with a service account key.",
+            ));
+        }
+
+        let valid_until = window.valid_from + window.expires_in;
+        let expires_in = valid_until
+            .duration_since(self.clock.now())
+            .unwrap_or_default();
         let opts = SignedURLOptions {
-            expires: expiration,
+            expires: Duration::from_secs(expires_in.as_secs().max(1)),
             method: SignedURLMethod::GET,
-            query_parameters: query_params,
+            query_parameters: query_params
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), vec![v.to_string()]))
+                .collect(),
             ..Default::default()
         };
 
-        let signed_url = self
-            .client
-            .signed_url(uri.bucket(), uri.key(), None, None, opts)
-            .await
-            .map_err(|e| SignerError::other_error(e.to_string()))?;
-        Ok(PresignedUrl::new(signed_url, valid_from, expiration))
+        // The client places the object name in the URL path as is, so it has
+        // to be percent-encoded up front. It dates the signature itself, so
+        // the reported validity window is read back from the signed URL.
+        let signed_url = client
+            .signed_url(uri.bucket(), &encode_key(uri.key()), None, None, opts)
+            .await?;
+        Ok(PresignedUrl::parse(&signed_url)?.with_permission(Permission::Read))
     }
 }
 
@@ -77,12 +234,394 @@ impl CloudFileSigner for GcpFileSigner {
         expiration: Duration,
         permission: Permission,
     ) -> Result<PresignedUrl, SignerError> {
-        let uri = path.parse::<GcpUri>()?;
-        match permission {
-            Permission::Read => self.sign_read_request(&uri, valid_from, expiration).await,
-            Permission::Write => Err(SignerError::permission_not_supported(
-                "GCP does not support write permissions",
-            )),
+        self.sign_with_options(
+            path,
+            valid_from,
+            expiration,
+            permission,
+            &SignOptions::default(),
+        )
+        .await
+    }
+
+    async fn sign_with_options(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expiration: Duration,
+        permission: Permission,
+        options: &SignOptions,
+    ) -> Result<PresignedUrl, SignerError> {
+        instrument_sign(Provider::Gcs, permission, expiration, async {
+            let uri = path.parse::<GcpUri>()?;
+            record_bucket(uri.bucket());
+            GCS_VALIDITY_LIMITS.validate_at(valid_from, expiration, self.clock.now())?;
+            let window = GCS_VALIDITY_LIMITS.backdate(valid_from, expiration, self.clock_skew);
+            if options.server_side_encryption().is_some() {
+                return Err(SignerError::option_not_supported(
+                    "GCP does not support server-side encryption parameters in signed URLs.",
+                ));
+            }
+            if options.snapshot().is_some() {
+                return Err(SignerError::option_not_supported(
+                    "GCP does not support snapshots. Use a version id (generation) instead.",
+                ));
+            }
+
+            let generation = options.version_id().or(uri.generation());
+            match permission {
+                Permission::Read => self.sign_read_request(&uri, &window, generation).await,
+                Permission::Write => Err(SignerError::permission_not_supported(
+                    "GCP does not support write permissions",
+                )),
+            }
+        })
+        .await
+    }
+    fn sign_many_concurrency(&self) -> usize {
+        self.sign_many_concurrency
+    }
+}
+
+impl From<SignedURLError> for SignerError {
+    fn from(e: SignedURLError) -> Self {
+        let message = format!("Google Cloud Storage Error: {}", e);
+        let status = match &e {
+            SignedURLError::SignBlob(http::Error::Response(response)) => Some(response.code),
+            SignedURLError::SignBlob(http::Error::HttpClient(e)) => {
+                e.status().map(|status| status.as_u16())
+            }
+            _ => None,
+        };
+        let error = match (&e, status) {
+            (_, Some(429)) => Self::throttled(message),
+            (_, Some(401 | 403)) => Self::credentials_error(message),
+            (_, Some(500..=599)) => Self::network_error(message),
+            (SignedURLError::SignBlob(http::Error::HttpClient(_)), None) => {
+                Self::network_error(message)
+            }
+            (SignedURLError::SignBlob(http::Error::TokenSource(_)), _)
+            | (SignedURLError::CertError(_), _) => Self::credentials_error(message),
+            _ => Self::signing_error(message),
+        };
+        error.with_source(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::UNIX_EPOCH;
+
+    use google_cloud_storage::http::error::ErrorResponse;
+    use google_cloud_storage::sign::SignBy;
+
+    use crate::encoding::query_pairs;
+    use crate::parse::parse_v4_date;
+    use crate::{ManualClock, SignerErrorKind, TimeBucketedSigner};
+
+    use super::*;
+
+    const PRIVATE_KEY: &str = include_str!("../../tests/fixtures/gcs_private_key.pem");
+
+    fn config() -> ClientConfig {
+        ClientConfig {
+            default_google_access_id: Some(String::from("signer@project.iam.gserviceaccount.com")),
+            default_sign_by: Some(SignBy::PrivateKey(PRIVATE_KEY.as_bytes().to_vec())),
+            ..ClientConfig::default().anonymous()
+        }
+    }
+
+    /// A signer that signs with the service account key.
+    fn signer() -> GcpFileSigner {
+        GcpFileSigner::from_client_config(config())
+    }
+
+    /// A signer that signs with the Google Cloud Storage client.
+    fn client_signer() -> GcpFileSigner {
+        GcpFileSigner::new(Client::new(config()))
+    }
+
+    fn epoch(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn x_goog_param(url: &PresignedUrl, name: &str) -> String {
+        let values = query(url)
+            .into_iter()
+            .filter(|(k, _)| k == name)
+            .map(|(_, v)| v)
+            .collect::<Vec<_>>();
+        assert_eq!(values.len(), 1);
+        values[0].clone()
+    }
+
+    fn query(url: &PresignedUrl) -> Vec<(String, String)> {
+        let (_, query) = url.expose_url().split_once('?').unwrap();
+        query_pairs(query).unwrap()
+    }
+
+    fn response_error(code: u16) -> SignerError {
+        let response = ErrorResponse {
+            code,
+            errors: Vec::new(),
+            message: String::from("error"),
+        };
+        SignedURLError::SignBlob(http::Error::Response(response)).into()
+    }
+
+    #[test]
+    fn classify_sign_blob_errors() {
+        assert_eq!(response_error(429).kind(), SignerErrorKind::Throttled);
+        assert_eq!(response_error(503).kind(), SignerErrorKind::Network);
+        assert_eq!(
+            response_error(401).kind(),
+            SignerErrorKind::CredentialsError
+        );
+        assert_eq!(
+            response_error(403).kind(),
+            SignerErrorKind::CredentialsError
+        );
+        assert_eq!(response_error(400).kind(), SignerErrorKind::SigningError);
+        assert!(response_error(429).is_retryable());
+
+        let token_source = http::Error::TokenSource("no token".into());
+        assert_eq!(
+            SignerError::from(SignedURLError::SignBlob(token_source)).kind(),
+            SignerErrorKind::CredentialsError
+        );
+        assert_eq!(
+            SignerError::from(SignedURLError::InvalidOption("expires")).kind(),
+            SignerErrorKind::SigningError
+        );
+    }
+
+    #[tokio::test]
+    async fn classify_transport_errors() {
+        // Nothing listens on port 1, so the connection is refused.
+        let e = reqwest::Client::new()
+            .get("http://127.0.0.1:1/")
+            .send()
+            .await
+            .unwrap_err();
+        let error = SignerError::from(SignedURLError::SignBlob(http::Error::HttpClient(e)));
+        assert_eq!(error.kind(), SignerErrorKind::Network);
+        assert!(error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn x_goog_date_matches_valid_from() {
+        let signer = signer().with_clock(ManualClock::new(epoch(1_700_000_100)));
+        let url = signer
+            .sign(
+                "gs://bucket/key",
+                epoch(1_700_000_000),
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+            .await
+            .unwrap();
+
+        let date = x_goog_param(&url, "X-Goog-Date");
+        assert_eq!(parse_v4_date(&date).unwrap(), epoch(1_700_000_000));
+        assert_eq!(x_goog_param(&url, "X-Goog-Expires"), "3600");
+        assert_eq!(url.valid_from(), epoch(1_700_000_000));
+        assert_eq!(url.valid_until(), epoch(1_700_003_600));
+    }
+
+    #[tokio::test]
+    async fn clock_skew_backdates_x_goog_date() {
+        let signer = signer()
+            .with_clock(ManualClock::new(epoch(1_700_000_000)))
+            .with_clock_skew(Duration::from_secs(300));
+        let url = signer
+            .sign(
+                "gs://bucket/key",
+                epoch(1_700_000_000),
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+            .await
+            .unwrap();
+
+        let date = x_goog_param(&url, "X-Goog-Date");
+        assert_eq!(parse_v4_date(&date).unwrap(), epoch(1_699_999_700));
+        assert_eq!(x_goog_param(&url, "X-Goog-Expires"), "3900");
+        assert_eq!(url.valid_from(), epoch(1_700_000_000));
+        assert_eq!(url.valid_until(), epoch(1_700_003_600));
+    }
+
+    #[tokio::test]
+    async fn service_account_signatures_are_deterministic() {
+        let signer = signer().with_clock(ManualClock::new(epoch(1_700_000_000)));
+        let sign = || {
+            signer.sign(
+                "gs://bucket/key",
+                epoch(1_700_000_000),
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+        };
+        let first = sign().await.unwrap();
+        let second = sign().await.unwrap();
+        assert_eq!(first.expose_url(), second.expose_url());
+    }
+
+    #[tokio::test]
+    async fn bucketed_urls_are_identical() {
+        let clock = Arc::new(ManualClock::new(epoch(1_700_000_200)));
+        let signer = TimeBucketedSigner::new(
+            signer().with_clock(Arc::clone(&clock)),
+            Duration::from_secs(900),
+        );
+        let sign = |now: SystemTime| {
+            signer.sign(
+                "gs://bucket/key",
+                now,
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+        };
+
+        let first = sign(epoch(1_700_000_200)).await.unwrap();
+        clock.set(epoch(1_700_000_400));
+        let second = sign(epoch(1_700_000_400)).await.unwrap();
+        assert_eq!(first.expose_url(), second.expose_url());
+        assert_eq!(first.valid_from(), epoch(1_700_000_100));
+        assert_eq!(x_goog_param(&first, "X-Goog-Date"), "20231114T221500Z");
+
+        // The next bucket is signed with a new window.
+        clock.set(epoch(1_700_001_000));
+        let third = sign(epoch(1_700_001_000)).await.unwrap();
+        assert_ne!(first.expose_url(), third.expose_url());
+    }
+
+    #[tokio::test]
+    async fn client_signer_rejects_custom_clock_and_skew() {
+        let now = SystemTime::now();
+        let sign = |signer: GcpFileSigner| async move {
+            signer
+                .sign(
+                    "gs://bucket/key",
+                    now,
+                    Duration::from_secs(3600),
+                    Permission::Read,
+                )
+                .await
+        };
+
+        let err = sign(client_signer().with_clock(ManualClock::new(now)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::OptionNotSupported);
+        let err = sign(client_signer().with_clock_skew(Duration::from_secs(300)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::OptionNotSupported);
+
+        // The client dates the signature with whole seconds of the system time.
+        let url = sign(client_signer()).await.unwrap();
+        let date = x_goog_param(&url, "X-Goog-Date");
+        assert_eq!(parse_v4_date(&date).unwrap(), url.valid_from());
+        let requested_until = now + Duration::from_secs(3600);
+        assert!(url.valid_until() <= requested_until + Duration::from_secs(1));
+        assert!(url.valid_until() + Duration::from_secs(2) >= requested_until);
+    }
+
+    #[test]
+    fn sign_many_concurrency() {
+        assert_eq!(signer().sign_many_concurrency(), GCS_SIGN_MANY_CONCURRENCY);
+        assert_eq!(
+            signer()
+                .with_sign_many_concurrency(0)
+                .sign_many_concurrency(),
+            1
+        );
+    }
+
+    /// Compute the signature the client produces for a `GET` request with
+    /// the given query string, which excludes `X-Goog-Signature`.
+    fn expected_signature(host: &str, path: &str, query: &str) -> String {
+        use rsa::pkcs1v15::SigningKey;
+        use rsa::pkcs8::DecodePrivateKey;
+        use rsa::signature::{SignatureEncoding, Signer};
+        use sha2::{Digest, Sha256};
+
+        let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        let pairs = query_pairs(query).unwrap();
+        let param = |name: &str| {
+            pairs
+                .iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.clone())
+                .unwrap()
+        };
+        let credential = param("X-Goog-Credential");
+        let (_, scope) = credential.split_once('/').unwrap();
+        let canonical_request =
+            format!("GET\n{path}\n{query}\nhost:{host}\n\nhost\nUNSIGNED-PAYLOAD");
+        let string_to_sign = format!(
+            "GOOG4-RSA-SHA256\n{}\n{scope}\n{}",
+            param("X-Goog-Date"),
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let key = SigningKey::<Sha256>::from_pkcs8_pem(PRIVATE_KEY).unwrap();
+        hex(&key.sign(string_to_sign.as_bytes()).to_vec())
+    }
+
+    #[tokio::test]
+    async fn billing_project_is_signed() {
+        for signer in [signer(), client_signer()] {
+            assert_billing_project_is_signed(signer).await;
         }
     }
+
+    /// Check that the billing project is covered by a signature that matches
+    /// the V4 signing process.
+    async fn assert_billing_project_is_signed(signer: GcpFileSigner) {
+        let url = signer
+            .with_billing_project("my-project")
+            .sign(
+                "gs://bucket/key",
+                SystemTime::now(),
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+            .await
+            .unwrap();
+        assert!(query(&url)
+            .iter()
+            .any(|(k, v)| k == "userProject" && v == "my-project"));
+
+        let (base, query) = url.expose_url().split_once('?').unwrap();
+        let (query, signature) = query.split_once("&X-Goog-Signature=").unwrap();
+        let (host, path) = base
+            .strip_prefix("https://")
+            .unwrap()
+            .split_once('/')
+            .unwrap();
+        let path = format!("/{path}");
+        assert_eq!(expected_signature(host, &path, query), signature);
+
+        // Removing the billing project invalidates the signature.
+        let unbilled = query
+            .split('&')
+            .filter(|pair| !pair.starts_with("userProject="))
+            .collect::<Vec<_>>()
+            .join("&");
+        assert_ne!(expected_signature(host, &path, &unbilled), signature);
+    }
+
+    #[tokio::test]
+    async fn future_start_is_rejected() {
+        let err = signer()
+            .sign(
+                "gs://bucket/key",
+                SystemTime::now() + Duration::from_secs(3600),
+                Duration::from_secs(3600),
+                Permission::Read,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::InvalidStartTime);
+    }
 }
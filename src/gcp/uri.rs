@@ -2,12 +2,17 @@ use std::str::FromStr;
 
 use http::Uri;
 
+use crate::encoding::{decode, query_param};
 use crate::SignerError;
 
+const GCS_API_HOST: &str = "storage.googleapis.com";
+const GCS_BROWSER_HOST: &str = "storage.cloud.google.com";
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct GcpUri {
     bucket: String,
     key: String,
+    generation: Option<String>,
 }
 
 impl GcpUri {
@@ -15,6 +20,7 @@ impl GcpUri {
         Self {
             bucket: bucket.into(),
             key: key.into(),
+            generation: None,
         }
     }
 
@@ -26,17 +32,68 @@ impl GcpUri {
         &self.key
     }
 
-    fn parse_gs_uri(uri: Uri) -> Result<Self, SignerError> {
-        let bucket = uri
-            .host()
-            .ok_or(SignerError::uri_parse_error("Invalid URI: missing bucket"))?;
+    pub fn generation(&self) -> Option<&str> {
+        self.generation.as_deref()
+    }
+
+    /// Parse a `gs://<bucket>/<key>` URI. The key is taken verbatim, without
+    /// percent-decoding.
+    fn parse_gs_uri(uri: &str) -> Result<Self, SignerError> {
+        let (bucket, key) = uri
+            .split_once("://")
+            .and_then(|(_, rest)| rest.split_once('/'))
+            .filter(|(bucket, _)| !bucket.is_empty())
+            .ok_or(SignerError::uri_parse_error(format!(
+                "Invalid URI: Couldn't extract bucket name and object key. Format the URI as `gs://<bucket>/<key>`. Received URI: `{uri}`."
+            )))?;
+
+        Ok(Self::new(bucket, key))
+    }
+
+    fn from_url(uri: &Uri) -> Result<Self, SignerError> {
+        let host = uri.host().ok_or(SignerError::uri_parse_error(format!(
+            "Invalid URI: missing host. Received URI: `{uri}`."
+        )))?;
 
+        let gcp_uri = match host {
+            GCS_API_HOST | GCS_BROWSER_HOST => Self::parse_path_style_url(uri)?,
+            _ => match host.strip_suffix(GCS_API_HOST).and_then(|b| b.strip_suffix('.')) {
+                Some(bucket) if !bucket.is_empty() => {
+                    Self::parse_virtual_hosted_style_url(uri, bucket)?
+                }
+                _ => return Err(SignerError::uri_parse_error(format!(
+                    "Invalid URI. Hostname does not appear to be a valid GCS endpoint. Received host: `{host}`."
+                ))),
+            },
+        };
+
+        Ok(Self {
+            generation: query_param(uri, "generation")?,
+            ..gcp_uri
+        })
+    }
+
+    fn parse_path_style_url(uri: &Uri) -> Result<Self, SignerError> {
+        let (bucket, key) = uri
+            .path()
+            .strip_prefix('/')
+            .and_then(|p| p.split_once('/'))
+            .ok_or(SignerError::uri_parse_error(format!(
+                "Invalid URI: Couldn't extract bucket name and object key. Format the URI as `https://{GCS_API_HOST}/<bucket>/<key>`. Received URI: `{uri}`."
+            )))?;
+
+        Ok(Self::new(decode(bucket)?, decode(key)?))
+    }
+
+    fn parse_virtual_hosted_style_url(uri: &Uri, bucket: &str) -> Result<Self, SignerError> {
         let key = uri
             .path()
             .strip_prefix('/')
-            .ok_or(SignerError::uri_parse_error("Invalid URI: bad key"))?;
+            .ok_or(SignerError::uri_parse_error(format!(
+                "Invalid URI: Couldn't extract object key. Format the URI as `https://<bucket>.{GCS_API_HOST}/<key>`. Received URI: `{uri}`."
+            )))?;
 
-        Ok(Self::new(bucket.to_string(), key.to_string()))
+        Ok(Self::new(bucket, decode(key)?))
     }
 }
 
@@ -44,20 +101,28 @@ impl FromStr for GcpUri {
     type Err = SignerError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(("gs", _)) = s.split_once("://") {
+            return Self::parse_gs_uri(s);
+        }
+
         let uri = Uri::from_str(s).map_err(|e| SignerError::uri_parse_error(e.to_string()))?;
         match uri.scheme_str() {
-            Some("gs") => Self::parse_gs_uri(uri),
+            Some("http" | "https") => Self::from_url(&uri),
             None => Err(SignerError::uri_parse_error(
-                format!("Invalid URI: missing scheme. The URI should start with `gs`. Received URI: `{s}`."))
+                format!("Invalid URI: missing scheme. The URI should start with `gs`, `http` or `https`. Received URI: `{s}`."))
             ),
             Some(unsupported_scheme) => Err(SignerError::uri_parse_error(
-                format!("Unsupported URI scheme. Supported schemas is `gs`. Received scheme: `{unsupported_scheme}`."),
-            )),}
+                format!("Unsupported URI scheme. Supported schemas are `gs`, `http` and `https`. Received scheme: `{unsupported_scheme}`."),
+            )),
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
+    use proptest::prelude::*;
+
+    use crate::encoding::encode_key;
     use crate::error::SignerErrorKind;
 
     use super::*;
@@ -70,6 +135,147 @@ mod test {
         assert_eq!(gcp_uri.key(), "key");
     }
 
+    #[test]
+    fn parse_gs_nested_key() {
+        let uri = "gs://bucket/key/nested";
+        let gcp_uri = uri.parse::<GcpUri>().unwrap();
+        assert_eq!(gcp_uri.bucket(), "bucket");
+        assert_eq!(gcp_uri.key(), "key/nested");
+    }
+
+    #[test]
+    fn parse_gs_raw_key() {
+        let uri = "gs://bucket/reports/Q1 2024/naïve+data#1.csv?x=100%25";
+        let gcp_uri = uri.parse::<GcpUri>().unwrap();
+        assert_eq!(gcp_uri.bucket(), "bucket");
+        assert_eq!(gcp_uri.key(), "reports/Q1 2024/naïve+data#1.csv?x=100%25");
+    }
+
+    #[test]
+    fn parse_gs_uri_without_key() {
+        let uri = "gs://bucket";
+        let uri_err = uri.parse::<GcpUri>().unwrap_err();
+        assert_eq!(uri_err.kind(), SignerErrorKind::CloudUriParseError);
+        assert_eq!(
+            uri_err.message(),
+            "Invalid URI: Couldn't extract bucket name and object key. Format the URI as `gs://<bucket>/<key>`. Received URI: `gs://bucket`."
+        );
+    }
+
+    #[test]
+    fn parse_http_scheme() {
+        let uri = "http://storage.googleapis.com/bucket/key";
+        let gcp_uri = uri.parse::<GcpUri>().unwrap();
+        assert_eq!(gcp_uri.bucket(), "bucket");
+        assert_eq!(gcp_uri.key(), "key");
+    }
+
+    #[test]
+    fn parse_path_style() {
+        let uri = "https://storage.googleapis.com/bucket/key/nested";
+        let gcp_uri = uri.parse::<GcpUri>().unwrap();
+        assert_eq!(gcp_uri.bucket(), "bucket");
+        assert_eq!(gcp_uri.key(), "key/nested");
+    }
+
+    #[test]
+    fn parse_virtual_hosted_style() {
+        let uri = "https://bucket.storage.googleapis.com/key/nested";
+        let gcp_uri = uri.parse::<GcpUri>().unwrap();
+        assert_eq!(gcp_uri.bucket(), "bucket");
+        assert_eq!(gcp_uri.key(), "key/nested");
+    }
+
+    #[test]
+    fn parse_virtual_hosted_style_dotted_bucket() {
+        let uri = "https://my.dotted.bucket.storage.googleapis.com/key";
+        let gcp_uri = uri.parse::<GcpUri>().unwrap();
+        assert_eq!(gcp_uri.bucket(), "my.dotted.bucket");
+        assert_eq!(gcp_uri.key(), "key");
+    }
+
+    #[test]
+    fn parse_authenticated_browser_url() {
+        let uri = "https://storage.cloud.google.com/bucket/key/nested";
+        let gcp_uri = uri.parse::<GcpUri>().unwrap();
+        assert_eq!(gcp_uri.bucket(), "bucket");
+        assert_eq!(gcp_uri.key(), "key/nested");
+    }
+
+    #[test]
+    fn parse_url_with_generation() {
+        let uri = "https://storage.googleapis.com/bucket/key?generation=1360887697105000";
+        let gcp_uri = uri.parse::<GcpUri>().unwrap();
+        assert_eq!(gcp_uri.bucket(), "bucket");
+        assert_eq!(gcp_uri.key(), "key");
+        assert_eq!(gcp_uri.generation(), Some("1360887697105000"));
+    }
+
+    #[test]
+    fn parse_url_ignores_other_query_parameters() {
+        let uri = "https://bucket.storage.googleapis.com/key?alt=media";
+        let gcp_uri = uri.parse::<GcpUri>().unwrap();
+        assert_eq!(gcp_uri.key(), "key");
+        assert_eq!(gcp_uri.generation(), None);
+    }
+
+    #[test]
+    fn parse_percent_encoded_key() {
+        let uri =
+            "https://storage.googleapis.com/bucket/reports/Q1%202024/na%C3%AFve%2Bdata%231.csv";
+        let gcp_uri = uri.parse::<GcpUri>().unwrap();
+        assert_eq!(gcp_uri.bucket(), "bucket");
+        assert_eq!(gcp_uri.key(), "reports/Q1 2024/naïve+data#1.csv");
+    }
+
+    #[test]
+    fn parse_percent_encoded_slash_in_key() {
+        let uri = "https://bucket.storage.googleapis.com/folder%2Fobject";
+        let gcp_uri = uri.parse::<GcpUri>().unwrap();
+        assert_eq!(gcp_uri.bucket(), "bucket");
+        assert_eq!(gcp_uri.key(), "folder/object");
+    }
+
+    #[test]
+    fn parse_invalid_utf8_key() {
+        let uri = "https://storage.googleapis.com/bucket/%FF";
+        let uri_err = uri.parse::<GcpUri>().unwrap_err();
+        assert_eq!(uri_err.kind(), SignerErrorKind::CloudUriParseError);
+    }
+
+    #[test]
+    fn parse_path_style_without_key() {
+        let uri = "https://storage.googleapis.com/bucket";
+        let uri_err = uri.parse::<GcpUri>().unwrap_err();
+        assert_eq!(uri_err.kind(), SignerErrorKind::CloudUriParseError);
+        assert_eq!(
+            uri_err.message(),
+            "Invalid URI: Couldn't extract bucket name and object key. Format the URI as `https://storage.googleapis.com/<bucket>/<key>`. Received URI: `https://storage.googleapis.com/bucket`."
+        );
+    }
+
+    #[test]
+    fn parse_unknown_host() {
+        let uri = "https://bucket.s3.amazonaws.com/key";
+        let uri_err = uri.parse::<GcpUri>().unwrap_err();
+        assert_eq!(uri_err.kind(), SignerErrorKind::CloudUriParseError);
+        assert_eq!(
+            uri_err.message(),
+            "Invalid URI. Hostname does not appear to be a valid GCS endpoint. Received host: `bucket.s3.amazonaws.com`."
+        );
+    }
+
+    #[test]
+    fn parse_uri_without_scheme() {
+        let uri = "bucket";
+        let uri_err = uri.parse::<GcpUri>().unwrap_err();
+        assert_eq!(uri_err.kind(), SignerErrorKind::CloudUriParseError);
+        assert_eq!(
+            uri_err.message(),
+            "Invalid URI: missing scheme. The URI should start with `gs`, `http` or `https`. Received URI: `bucket`."
+        );
+    }
+
     #[test]
     fn parse_unsupported_scheme() {
         let uri = "invalid://bucket/key";
@@ -77,7 +283,22 @@ mod test {
         assert_eq!(uri_err.kind(), SignerErrorKind::CloudUriParseError);
         assert_eq!(
             uri_err.message(),
-            "Unsupported URI scheme. Supported schemas is `gs`. Received scheme: `invalid`."
+            "Unsupported URI scheme. Supported schemas are `gs`, `http` and `https`. Received scheme: `invalid`."
         )
     }
+
+    proptest! {
+        #[test]
+        fn gs_uri_key_round_trips(key in "\\PC*") {
+            let gcp_uri = format!("gs://bucket/{key}").parse::<GcpUri>().unwrap();
+            prop_assert_eq!(gcp_uri.key(), key);
+        }
+
+        #[test]
+        fn url_key_round_trips(key in "\\PC*") {
+            let url = format!("https://bucket.storage.googleapis.com/{}", encode_key(&key));
+            let gcp_uri = url.parse::<GcpUri>().unwrap();
+            prop_assert_eq!(gcp_uri.key(), key);
+        }
+    }
 }
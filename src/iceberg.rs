@@ -0,0 +1,257 @@
+//! Helpers for vending access to files through an Iceberg REST catalog.
+//!
+//! An Iceberg REST catalog hands out access to the files of a table through
+//! the `config` map of a table response. This module builds that map in one
+//! of two modes:
+//!
+//! - Presigned URL mode, in which every file, such as the manifest list and
+//!   the data files, gets its own presigned URL. Iceberg does not define
+//!   properties for presigned URLs, so this mode needs a client that
+//!   understands the keys. Any [`CloudFileSigner`] can be used, including
+//!   [`AwsFileSigner`](crate::aws::AwsFileSigner),
+//!   [`AbfsFileSigner`](crate::azure::AbfsFileSigner) and
+//!   [`GcpFileSigner`](crate::gcp::GcpFileSigner).
+//! - Scoped credential mode, in which the client receives short-lived
+//!   credentials under the property names of the Iceberg `FileIO`
+//!   implementations.
+//!
+//! # Example
+//! ```rust
+//! use std::time::{Duration, SystemTime};
+//! use cloud_file_signer::credentials::AwsCredentials;
+//! use cloud_file_signer::iceberg::{scoped_credential_config, S3_SESSION_TOKEN};
+//!
+//! let credentials = AwsCredentials::new("ASIAEXAMPLE", "secret")
+//!     .with_session_token("token")
+//!     .with_region("eu-west-1")
+//!     .with_expires_at(SystemTime::now() + Duration::from_secs(3600));
+//! let config = scoped_credential_config(&credentials.into());
+//! assert_eq!(config[S3_SESSION_TOKEN], "token");
+//! ```
+
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::credentials::ScopedCredentials;
+use crate::{CloudFileSigner, Permission, SignerError};
+
+/// The access key id for `S3FileIO`.
+pub const S3_ACCESS_KEY_ID: &str = "s3.access-key-id";
+/// The secret access key for `S3FileIO`.
+pub const S3_SECRET_ACCESS_KEY: &str = "s3.secret-access-key";
+/// The session token for `S3FileIO`.
+pub const S3_SESSION_TOKEN: &str = "s3.session-token";
+/// The expiry of the session token for `S3FileIO`, in milliseconds since
+/// the Unix epoch.
+pub const S3_SESSION_TOKEN_EXPIRES_AT_MS: &str = "s3.session-token-expires-at-ms";
+/// The region of the AWS clients.
+pub const CLIENT_REGION: &str = "client.region";
+/// The prefix of the SAS token for `ADLSFileIO`. The key is completed with
+/// the host of the storage account, such as
+/// `account.dfs.core.windows.net`.
+pub const ADLS_SAS_TOKEN_PREFIX: &str = "adls.sas-token.";
+/// The prefix of the expiry of the SAS token for `ADLSFileIO`, in
+/// milliseconds since the Unix epoch. The key is completed with the host of
+/// the storage account.
+pub const ADLS_SAS_TOKEN_EXPIRES_AT_MS_PREFIX: &str = "adls.sas-token-expires-at-ms.";
+/// The OAuth 2.0 access token for `GCSFileIO`.
+pub const GCS_OAUTH2_TOKEN: &str = "gcs.oauth2.token";
+/// The expiry of the OAuth 2.0 access token for `GCSFileIO`, in
+/// milliseconds since the Unix epoch.
+pub const GCS_OAUTH2_TOKEN_EXPIRES_AT: &str = "gcs.oauth2.token-expires-at";
+/// The project for `GCSFileIO`.
+pub const GCS_PROJECT_ID: &str = "gcs.project-id";
+/// The prefix of the presigned URL of a file. Iceberg does not define a
+/// property for presigned URLs, so the key is completed with the path of
+/// the file as it appears in the table metadata.
+pub const PRESIGNED_URL_PREFIX: &str = "presigned-url.";
+
+const ADLS_HOST_SUFFIX: &str = ".dfs.core.windows.net";
+
+/// Build the `config` map that gives read access to `paths` through
+/// presigned URLs. The paths are typically the manifest list of a snapshot,
+/// its manifests and data files.
+///
+/// Every path is mapped to its presigned URL under the key
+/// [`PRESIGNED_URL_PREFIX`]`<path>`. Returns the first error if any of the
+/// paths could not be signed.
+///
+/// No Iceberg client reads these keys: the Iceberg `FileIO`
+/// implementations only understand scoped credentials. The map is meant
+/// for catalogs and clients that agree on the `presigned-url.` convention,
+/// for example a custom `FileIO` that looks up the URL of every file it
+/// opens. Use [`scoped_credential_config`] for stock Iceberg clients.
+pub async fn presigned_url_config<S: CloudFileSigner + ?Sized>(
+    signer: &S,
+    paths: &[&str],
+    valid_from: SystemTime,
+    expires_in: Duration,
+) -> Result<BTreeMap<String, String>, SignerError> {
+    signer
+        .sign_many(paths, valid_from, expires_in, Permission::Read)
+        .await
+        .into_iter()
+        .zip(paths)
+        .map(|(presigned_url, path)| {
+            Ok((
+                format!("{PRESIGNED_URL_PREFIX}{path}"),
+                presigned_url?.into_string(),
+            ))
+        })
+        .collect()
+}
+
+/// Build the `config` map that hands `credentials` to an Iceberg client,
+/// using the property names of the Iceberg `FileIO` implementations.
+#[must_use]
+pub fn scoped_credential_config(credentials: &ScopedCredentials) -> BTreeMap<String, String> {
+    let mut config = BTreeMap::new();
+    match credentials {
+        ScopedCredentials::Aws(aws) => {
+            config.insert(S3_ACCESS_KEY_ID.into(), aws.access_key_id().into());
+            config.insert(S3_SECRET_ACCESS_KEY.into(), aws.secret_access_key().into());
+            if let Some(session_token) = aws.session_token() {
+                config.insert(S3_SESSION_TOKEN.into(), session_token.into());
+            }
+            if let Some(expires_at) = aws.expires_at() {
+                config.insert(
+                    S3_SESSION_TOKEN_EXPIRES_AT_MS.into(),
+                    epoch_millis(expires_at),
+                );
+            }
+            if let Some(region) = aws.region() {
+                config.insert(CLIENT_REGION.into(), region.into());
+            }
+        }
+        ScopedCredentials::Azure(azure) => {
+            let host = format!("{}{ADLS_HOST_SUFFIX}", azure.storage_account());
+            config.insert(
+                format!("{ADLS_SAS_TOKEN_PREFIX}{host}"),
+                azure.sas_token().into(),
+            );
+            if let Some(expires_at) = azure.expires_at() {
+                config.insert(
+                    format!("{ADLS_SAS_TOKEN_EXPIRES_AT_MS_PREFIX}{host}"),
+                    epoch_millis(expires_at),
+                );
+            }
+        }
+        ScopedCredentials::Gcp(gcp) => {
+            config.insert(GCS_OAUTH2_TOKEN.into(), gcp.access_token().into());
+            if let Some(expires_at) = gcp.expires_at() {
+                config.insert(GCS_OAUTH2_TOKEN_EXPIRES_AT.into(), epoch_millis(expires_at));
+            }
+            if let Some(project_id) = gcp.project_id() {
+                config.insert(GCS_PROJECT_ID.into(), project_id.into());
+            }
+        }
+    }
+    config
+}
+
+fn epoch_millis(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis())
+        .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::credentials::{AwsCredentials, AzureSasCredentials, GcpCredentials};
+    use crate::testing::MockSigner;
+
+    use super::*;
+
+    fn epoch(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[tokio::test]
+    async fn presigned_urls() {
+        let paths = vec![
+            "s3://bucket/table/metadata/snap-1-manifest-list.avro",
+            "s3://bucket/table/data/00000-0-data.parquet",
+        ];
+        let config = presigned_url_config(
+            &MockSigner,
+            &paths,
+            epoch(1704067200),
+            Duration::from_secs(60),
+        )
+        .await
+        .unwrap();
+        assert_eq!(config.len(), 2);
+        assert_eq!(
+            config["presigned-url.s3://bucket/table/data/00000-0-data.parquet"],
+            "s3://bucket/table/data/00000-0-data.parquet?permission=read&valid_from=1704067200&expires_in=60"
+        );
+    }
+
+    #[tokio::test]
+    async fn presigned_urls_fail_on_error() {
+        let paths = vec!["s3://bucket/a", "relative/b"];
+        let result = presigned_url_config(
+            &MockSigner,
+            &paths,
+            SystemTime::now(),
+            Duration::from_secs(60),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn aws_credentials() {
+        let credentials = AwsCredentials::new("ASIAEXAMPLE", "secret")
+            .with_session_token("token")
+            .with_region("eu-west-1")
+            .with_expires_at(epoch(1704067200));
+        let config = scoped_credential_config(&credentials.into());
+        assert_eq!(
+            config,
+            BTreeMap::from([
+                ("client.region".to_string(), "eu-west-1".to_string()),
+                ("s3.access-key-id".to_string(), "ASIAEXAMPLE".to_string()),
+                ("s3.secret-access-key".to_string(), "secret".to_string()),
+                ("s3.session-token".to_string(), "token".to_string()),
+                (
+                    "s3.session-token-expires-at-ms".to_string(),
+                    "1704067200000".to_string()
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn azure_credentials() {
+        let credentials = AzureSasCredentials::new("account", "sv=2022-11-02&sig=abc")
+            .with_expires_at(epoch(1704067200));
+        let config = scoped_credential_config(&credentials.into());
+        assert_eq!(
+            config,
+            BTreeMap::from([
+                (
+                    "adls.sas-token.account.dfs.core.windows.net".to_string(),
+                    "sv=2022-11-02&sig=abc".to_string()
+                ),
+                (
+                    "adls.sas-token-expires-at-ms.account.dfs.core.windows.net".to_string(),
+                    "1704067200000".to_string()
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn gcp_credentials() {
+        let credentials = GcpCredentials::new("ya29.token").with_project_id("my-project");
+        let config = scoped_credential_config(&credentials.into());
+        assert_eq!(
+            config,
+            BTreeMap::from([
+                ("gcs.oauth2.token".to_string(), "ya29.token".to_string()),
+                ("gcs.project-id".to_string(), "my-project".to_string()),
+            ])
+        );
+    }
+}
@@ -37,10 +37,71 @@
 //! the user, which gives the user read or write access to a specific object
 //! for a limited amount of time.
 //!
+//! ## Object keys
+//! Keys in cloud native URIs such as `s3://bucket/key` and `gs://bucket/key`
+//! are used verbatim. Keys in `http(s)://` URLs and in `abfs(s)://` URIs are
+//! percent-decoded before signing. Keys are always percent-encoded in the
+//! signed URL, so `s3://bucket/Q1 2024/data.csv` and
+//! `https://bucket.s3.amazonaws.com/Q1%202024/data.csv` both sign the object
+//! `Q1 2024/data.csv`.
+//!
+//! ## Validity windows
+//! The signers for Amazon S3, Azure Blob Storage and Google Cloud Storage
+//! check the requested validity window against the [`ValidityLimits`] of
+//! the provider before signing. The limits are exposed as
+//! [`aws::S3_VALIDITY_LIMITS`], [`azure::BLOB_VALIDITY_LIMITS`] and
+//! [`gcp::GCS_VALIDITY_LIMITS`].
+//!
+//! The S3, Azure and Google Cloud Storage signers can backdate the signed
+//! start time to tolerate clients whose clocks are behind, see
+//! `with_clock_skew`. Google Cloud Storage URLs cannot start in the future,
+//! and are only backdated when they are signed with a service account key:
+//! URLs signed by the Google Cloud Storage client are dated when they are
+//! signed. The signers read the current time through a [`Clock`], which
+//! tests can replace with a [`ManualClock`].
+//!
+//! ## Verifying signed URLs
+//! Signed URLs can be verified offline with the credentials that signed
+//! them. A [`UrlVerifier`] reports whether a URL is authentic, which
+//! permission and resource it grants and when it expires. Verifiers are
+//! provided for S3 (`aws::SigV4Verifier`), Azure Blob Storage SAS URLs
+//! (`azure::SasVerifier`) and Google Cloud Storage URLs signed with an HMAC
+//! key (`gcp::GcsHmacVerifier`).
+//!
 //! # `CloudFileSigner`
 //! The `CloudFileSigner` trait defines a uniform interface for signing
 //! URLs. Implementations of `CloudFileSigner` are provided for AWS S3,
-//! Azure Blob Storage and Google Cloud Storage.
+//! Amazon CloudFront, Azure Blob Storage, Google Cloud Storage and Google
+//! Cloud CDN.
+//!
+//! Many paths can be signed at once with [`CloudFileSigner::sign_many`], or
+//! as a stream with [`CloudFileSignerExt::sign_stream`] when the list of
+//! paths is too large to hold in memory.
+//!
+//! Signers can be wrapped in a [`TimeBucketedSigner`] to align the validity
+//! window of signed URLs to fixed time buckets. Identical requests within a
+//! bucket then yield identical, cacheable URLs, except for Google Cloud
+//! Storage URLs signed by the client instead of a service account key,
+//! whose signatures are dated when they are signed. A [`CachingSigner`]
+//! keeps recently signed URLs in memory and reuses them for requests whose
+//! validity window they cover. A [`RetryingSigner`] retries requests that
+//! failed with a transient error.
+//!
+//! Every request to a provider signer runs in a `sign_url` [`tracing`] span
+//! with the provider, bucket or container, permission and expiry, and
+//! records its outcome. Paths, signed URLs and credentials are never
+//! recorded. With the `metrics` feature, signers also emit the
+//! `cloud_file_signer_sign_requests_total` and
+//! `cloud_file_signer_sign_errors_total` counters and the
+//! `cloud_file_signer_sign_duration_seconds` histogram, labelled by
+//! provider, outcome and error kind.
+//!
+//! With the `serde` feature, [`PresignedUrl`], [`Permission`], [`Provider`]
+//! and [`SignerError`] implement `Serialize` and `Deserialize` with stable
+//! JSON representations, so signed URLs can be handed to other services.
+//!
+//! With the `testing` feature, the `testing` module provides signers for
+//! unit tests that do not need cloud credentials.
 
 #![warn(missing_docs)]
 #![warn(rustdoc::missing_crate_level_docs)]
@@ -51,17 +112,53 @@ use std::{
     time::{Duration, SystemTime},
 };
 
+use futures::{stream, StreamExt};
+
 pub mod aws;
 pub mod azure;
+pub mod credentials;
 pub mod gcp;
+pub mod iceberg;
+
+#[cfg(feature = "delta-sharing")]
+pub mod delta_sharing;
 
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+mod bucketed;
+mod caching;
+mod clock;
+mod encoding;
 mod error;
+mod options;
+mod parse;
 mod permissions;
 mod presigned_url;
+mod provider;
+mod retrying;
+mod streaming;
+mod telemetry;
+mod time;
+mod validation;
+mod verify;
 
+pub use bucketed::TimeBucketedSigner;
+pub use caching::{CacheStats, CachingSigner};
+pub use clock::{Clock, ManualClock, SystemClock};
 pub use error::{SignerError, SignerErrorKind};
+pub use options::{ServerSideEncryption, SignOptions};
 pub use permissions::Permission;
 pub use presigned_url::PresignedUrl;
+pub use provider::Provider;
+pub use retrying::{RetryPolicy, RetryingSigner};
+pub use streaming::CloudFileSignerExt;
+pub use validation::ValidityLimits;
+pub use verify::{UrlVerifier, Verification};
+
+/// The default maximum number of URLs that [`CloudFileSigner::sign_many`]
+/// signs concurrently, see [`CloudFileSigner::sign_many_concurrency`].
+pub const SIGN_MANY_CONCURRENCY: usize = 32;
 
 /// A trait for signing URLs for files in a cloud object store.
 #[async_trait::async_trait]
@@ -77,6 +174,63 @@ pub trait CloudFileSigner: Send + Sync {
         permission: Permission,
     ) -> Result<PresignedUrl, SignerError>;
 
+    /// Sign a URL for a file in a cloud object store, refined by the
+    /// given [`SignOptions`]. The URL is valid for the specified duration
+    /// and grants the specified permission.
+    ///
+    /// The default implementation only accepts empty options and
+    /// delegates to [`CloudFileSigner::sign`].
+    async fn sign_with_options(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+        options: &SignOptions,
+    ) -> Result<PresignedUrl, SignerError> {
+        if !options.is_empty() {
+            return Err(SignerError::option_not_supported(format!(
+                "The signer does not support the sign options: {}.",
+                options.names().join(", ")
+            )));
+        }
+        self.sign(path, valid_from, expires_in, permission).await
+    }
+
+    /// Sign URLs for many files in a cloud object store at once. All URLs
+    /// are valid for the specified duration and grant the specified
+    /// permission.
+    ///
+    /// The results are returned in the order of `paths`. A failure to sign
+    /// one path does not affect the other paths. At most
+    /// [`sign_many_concurrency`](CloudFileSigner::sign_many_concurrency)
+    /// URLs are signed concurrently.
+    ///
+    /// The default implementation calls [`CloudFileSigner::sign`] for every
+    /// path. Signers override it to share work across the batch.
+    async fn sign_many(
+        &self,
+        paths: &[&str],
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Vec<Result<PresignedUrl, SignerError>> {
+        let requests = paths
+            .iter()
+            .map(|path| self.sign(path, valid_from, expires_in, permission))
+            .collect::<Vec<_>>();
+        stream::iter(requests)
+            .buffered(self.sign_many_concurrency().max(1))
+            .collect()
+            .await
+    }
+
+    /// Return the maximum number of URLs that [`CloudFileSigner::sign_many`]
+    /// signs concurrently. Defaults to [`SIGN_MANY_CONCURRENCY`].
+    fn sign_many_concurrency(&self) -> usize {
+        SIGN_MANY_CONCURRENCY
+    }
+
     /// Sign a URL for a file in a cloud object store. The URL is valid
     /// for the specified duration and grants read permission.
     async fn sign_read_only_starting_now(
@@ -113,6 +267,35 @@ impl<S: CloudFileSigner + ?Sized> CloudFileSigner for Box<S> {
             .sign(path, valid_from, expires_in, permission)
             .await
     }
+
+    async fn sign_with_options(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+        options: &SignOptions,
+    ) -> Result<PresignedUrl, SignerError> {
+        (**self)
+            .sign_with_options(path, valid_from, expires_in, permission, options)
+            .await
+    }
+
+    async fn sign_many(
+        &self,
+        paths: &[&str],
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Vec<Result<PresignedUrl, SignerError>> {
+        (**self)
+            .sign_many(paths, valid_from, expires_in, permission)
+            .await
+    }
+
+    fn sign_many_concurrency(&self) -> usize {
+        (**self).sign_many_concurrency()
+    }
 }
 
 #[async_trait::async_trait]
@@ -128,4 +311,135 @@ impl<S: CloudFileSigner + ?Sized> CloudFileSigner for Arc<S> {
             .sign(path, valid_from, expires_in, permission)
             .await
     }
+
+    async fn sign_with_options(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+        options: &SignOptions,
+    ) -> Result<PresignedUrl, SignerError> {
+        (**self)
+            .sign_with_options(path, valid_from, expires_in, permission, options)
+            .await
+    }
+
+    async fn sign_many(
+        &self,
+        paths: &[&str],
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Vec<Result<PresignedUrl, SignerError>> {
+        (**self)
+            .sign_many(paths, valid_from, expires_in, permission)
+            .await
+    }
+
+    fn sign_many_concurrency(&self) -> usize {
+        (**self).sign_many_concurrency()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::SlowSigner;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn sign_many_keeps_input_order() {
+        let paths = (0..100)
+            .map(|i| {
+                if i % 10 == 0 {
+                    format!("invalid-{i}")
+                } else {
+                    format!("s3://bucket/{i}")
+                }
+            })
+            .collect::<Vec<_>>();
+        let paths = paths.iter().map(String::as_str).collect::<Vec<_>>();
+        let signer = SlowSigner::default();
+        let results = signer
+            .sign_many(
+                &paths,
+                SystemTime::now(),
+                Duration::from_secs(60),
+                Permission::Read,
+            )
+            .await;
+
+        assert_eq!(results.len(), paths.len());
+        for (path, result) in paths.iter().zip(results) {
+            match result {
+                Ok(presigned_url) => {
+                    assert!(presigned_url.expose_url().starts_with(&format!("{path}?")))
+                }
+                Err(e) => assert!(e.message().contains(path)),
+            }
+        }
+        let max_in_flight = signer.max_in_flight();
+        assert!(max_in_flight > 1);
+        assert!(max_in_flight <= SIGN_MANY_CONCURRENCY);
+    }
+
+    #[tokio::test]
+    async fn sign_many_respects_concurrency() {
+        let paths = vec!["s3://bucket/key"; 20];
+        let signer = Arc::new(SlowSigner::default().with_concurrency(2));
+        assert_eq!(signer.sign_many_concurrency(), 2);
+        let results = signer
+            .sign_many(
+                &paths,
+                SystemTime::now(),
+                Duration::from_secs(60),
+                Permission::Read,
+            )
+            .await;
+        assert!(results.iter().all(Result::is_ok));
+        assert!(signer.max_in_flight() <= 2);
+    }
+
+    #[tokio::test]
+    async fn sign_many_through_box() {
+        let signer: Box<dyn CloudFileSigner> = Box::new(SlowSigner::default());
+        let results = signer
+            .sign_many(
+                &["s3://bucket/key"],
+                SystemTime::now(),
+                Duration::from_secs(60),
+                Permission::Read,
+            )
+            .await;
+        assert!(results[0]
+            .as_ref()
+            .unwrap()
+            .expose_url()
+            .starts_with("s3://bucket/key?"));
+    }
+
+    #[tokio::test]
+    async fn unsupported_options_are_named_without_values() {
+        let options =
+            SignOptions::new().with_server_side_encryption(ServerSideEncryption::CustomerKey {
+                key: String::from("c2VjcmV0LWN1c3RvbWVyLWtleQ=="),
+                key_md5: String::from("bWQ1"),
+            });
+        let err = SlowSigner::default()
+            .sign_with_options(
+                "s3://bucket/key",
+                SystemTime::now(),
+                Duration::from_secs(60),
+                Permission::Read,
+                &options,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::OptionNotSupported);
+        assert_eq!(
+            err.message(),
+            "The signer does not support the sign options: server_side_encryption."
+        );
+    }
 }
@@ -0,0 +1,175 @@
+//! Options that refine how a URL is signed.
+
+use std::fmt::{Debug, Formatter};
+
+/// Options that refine how a URL is signed.
+///
+/// Not every signer supports every option. A signer returns an error of kind
+/// [`SignerErrorKind::OptionNotSupported`](crate::SignerErrorKind) when it is
+/// asked to apply an option it cannot honour, rather than silently ignoring
+/// it.
+///
+/// # Example
+/// ```rust
+/// use cloud_file_signer::SignOptions;
+///
+/// let options = SignOptions::new().with_version_id("3HL4kqtJlcpXroDTDmJ");
+/// assert_eq!(options.version_id(), Some("3HL4kqtJlcpXroDTDmJ"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SignOptions {
+    version_id: Option<String>,
+    snapshot: Option<String>,
+    server_side_encryption: Option<ServerSideEncryption>,
+}
+
+impl SignOptions {
+    /// Create a new set of options with nothing configured.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pin the signed URL to a specific version of the object.
+    ///
+    /// The version identifier maps to the `versionId` of an S3 object, the
+    /// `versionid` of an Azure blob and the `generation` of a GCS object.
+    /// Version pinning is only supported for read permissions.
+    #[must_use]
+    pub fn with_version_id(mut self, version_id: impl Into<String>) -> Self {
+        self.version_id = Some(version_id.into());
+        self
+    }
+
+    /// Pin the signed URL to a snapshot of an Azure blob.
+    ///
+    /// Snapshots are only supported by Azure Blob Storage, and only for read
+    /// permissions.
+    #[must_use]
+    pub fn with_snapshot(mut self, snapshot: impl Into<String>) -> Self {
+        self.snapshot = Some(snapshot.into());
+        self
+    }
+
+    /// Bind server-side encryption parameters to the signed request.
+    ///
+    /// The encryption parameters are sent as headers, so the client using
+    /// the signed URL has to send the headers returned by
+    /// [`PresignedUrl::required_headers`](crate::PresignedUrl::required_headers).
+    /// Server-side encryption parameters are only supported by Amazon S3.
+    #[must_use]
+    pub fn with_server_side_encryption(mut self, sse: ServerSideEncryption) -> Self {
+        self.server_side_encryption = Some(sse);
+        self
+    }
+
+    /// Return the version the signed URL is pinned to, if any.
+    #[must_use]
+    pub fn version_id(&self) -> Option<&str> {
+        self.version_id.as_deref()
+    }
+
+    /// Return the snapshot the signed URL is pinned to, if any.
+    #[must_use]
+    pub fn snapshot(&self) -> Option<&str> {
+        self.snapshot.as_deref()
+    }
+
+    /// Return the server-side encryption parameters, if any.
+    #[must_use]
+    pub fn server_side_encryption(&self) -> Option<&ServerSideEncryption> {
+        self.server_side_encryption.as_ref()
+    }
+
+    /// Return `true` if no options are configured.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Return the names of the configured options, without their values.
+    pub(crate) fn names(&self) -> Vec<&'static str> {
+        [
+            ("version_id", self.version_id.is_some()),
+            ("snapshot", self.snapshot.is_some()),
+            (
+                "server_side_encryption",
+                self.server_side_encryption.is_some(),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, configured)| configured.then_some(name))
+        .collect()
+    }
+}
+
+/// Server-side encryption parameters for a signed request.
+///
+/// # Example
+/// ```rust
+/// use cloud_file_signer::{ServerSideEncryption, SignOptions};
+///
+/// let options = SignOptions::new().with_server_side_encryption(ServerSideEncryption::Kms {
+///     key_id: Some("arn:aws:kms:us-east-1:111122223333:key/my-key".to_string()),
+/// });
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum ServerSideEncryption {
+    /// Encrypt a written object with keys managed by the object store
+    /// (SSE-S3). Only applies to write permissions.
+    Managed,
+    /// Encrypt a written object with a KMS key (SSE-KMS). When no key id
+    /// is given the default KMS key of the bucket is used. Only applies to
+    /// write permissions.
+    Kms {
+        /// The id or ARN of the KMS key.
+        key_id: Option<String>,
+    },
+    /// Encrypt or decrypt an object with a key provided by the customer
+    /// (SSE-C). Applies to both read and write permissions.
+    CustomerKey {
+        /// The base64-encoded 256-bit AES key.
+        key: String,
+        /// The base64-encoded MD5 digest of the key.
+        key_md5: String,
+    },
+}
+
+impl Debug for ServerSideEncryption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Managed => f.write_str("Managed"),
+            Self::Kms { key_id } => f.debug_struct("Kms").field("key_id", key_id).finish(),
+            Self::CustomerKey { key_md5, .. } => f
+                .debug_struct("CustomerKey")
+                .field("key_md5", key_md5)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn debug_hides_customer_key() {
+        let sse = ServerSideEncryption::CustomerKey {
+            key: String::from("c2VjcmV0LWN1c3RvbWVyLWtleQ=="),
+            key_md5: String::from("bWQ1"),
+        };
+        let options = SignOptions::new().with_server_side_encryption(sse);
+        let debug = format!("{options:?}");
+        assert!(!debug.contains("c2VjcmV0LWN1c3RvbWVyLWtleQ=="));
+        assert!(debug.contains("CustomerKey { key_md5: \"bWQ1\", .. }"));
+    }
+
+    #[test]
+    fn names_of_configured_options() {
+        assert!(SignOptions::new().names().is_empty());
+        let options = SignOptions::new()
+            .with_version_id("v1")
+            .with_server_side_encryption(ServerSideEncryption::Managed);
+        assert_eq!(options.names(), ["version_id", "server_side_encryption"]);
+    }
+}
@@ -11,7 +11,12 @@ use crate::error::SignerError;
 ///
 /// When an URL is signed, the signature also includes the
 /// actions that a use can do with the given URL.
+///
+/// With the `serde` feature, permissions are represented as `"read"` and
+/// `"write"`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Permission {
     /// The URL can be used to read the file.
     Read,
@@ -76,4 +81,18 @@ mod test {
             "`unknown permission` is not recognized as a valid permission for a presigned url."
         )
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_permission() {
+        assert_eq!(
+            serde_json::to_string(&Permission::Read).unwrap(),
+            r#""read""#
+        );
+        assert_eq!(
+            serde_json::from_str::<Permission>(r#""write""#).unwrap(),
+            Permission::Write
+        );
+        assert!(serde_json::from_str::<Permission>(r#""ReadOnly""#).is_err());
+    }
 }
@@ -1,10 +1,14 @@
 //! A presigned URL for a file in a (cloud) object store.
 
 use std::{
-    fmt::{Display, Formatter},
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt::{Debug, Display, Formatter},
     time::{Duration, SystemTime},
 };
 
+use crate::{Permission, Provider, SignerError};
+
 /// A presigned URL for a file in a (cloud) object store.
 ///
 /// A presigned URL is a URL that grants temporary access to a file in
@@ -16,12 +20,29 @@ use std::{
 /// expiry of the underlying credentials.
 ///
 /// A `PresignedUrl` is typically created by an implementor of the
-/// `CloudFileSigner` trait.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// `CloudFileSigner` trait, or parsed from a URL string with
+/// [`PresignedUrl::parse`].
+///
+/// With the `serde` feature, a `PresignedUrl` is represented as a JSON
+/// object with the `url`, the validity window as RFC 3339 timestamps
+/// (`validFrom`, `validUntil`) and as milliseconds since the Unix epoch
+/// (`validFromMillis`, `validUntilMillis`), and the `provider`,
+/// `permission`, `method`, `objectUri` and `requiredHeaders` when they are
+/// known. Either form of the timestamps is accepted when deserializing.
+///
+/// The signature in a presigned URL is a bearer credential. The `Debug` and
+/// `Display` representations therefore redact the signature and session
+/// token query parameters, so a `PresignedUrl` can safely be logged. Use
+/// [`PresignedUrl::expose_url`] to obtain the usable URL.
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct PresignedUrl {
     url: String,
     valid_from: SystemTime,
     valid_for_duration: Duration,
+    required_headers: BTreeMap<String, String>,
+    provider: Option<Provider>,
+    permission: Option<Permission>,
+    object_uri: Option<String>,
 }
 
 impl PresignedUrl {
@@ -49,10 +70,57 @@ impl PresignedUrl {
             url: url.into(),
             valid_from,
             valid_for_duration,
+            required_headers: BTreeMap::new(),
+            provider: None,
+            permission: None,
+            object_uri: None,
         }
     }
 
-    /// Return the presigned URL as a string.
+    /// Parse a signed URL that was issued elsewhere, without credentials.
+    ///
+    /// The provider is detected from the query parameters of the URL:
+    ///
+    /// - `X-Amz-*` parameters for S3 Signature Version 4,
+    /// - `se` and `sig` for Azure SAS tokens,
+    /// - `X-Goog-*` parameters for Google Cloud Storage V4 signatures,
+    /// - `Expires` or `Policy` with `Key-Pair-Id` for CloudFront,
+    /// - `Expires` with `KeyName` for Cloud CDN.
+    ///
+    /// The validity window, the permission and the object are read from the
+    /// URL where it states them. S3 URLs only state the permission when they
+    /// carry the `x-id` parameter of the AWS SDKs, and Google Cloud Storage
+    /// URLs never do. URLs that do not state a start time, such as
+    /// CloudFront canned policies, are treated as valid from the moment they
+    /// are parsed.
+    ///
+    /// The signature is not checked, see [`UrlVerifier`](crate::UrlVerifier)
+    /// for that.
+    ///
+    /// # Example
+    /// ```rust
+    /// use std::time::{Duration, UNIX_EPOCH};
+    /// use cloud_file_signer::{Permission, PresignedUrl, Provider};
+    ///
+    /// let presigned_url = PresignedUrl::parse(
+    ///     "https://account.blob.core.windows.net/container/file.csv?sv=2022-11-02&st=2024-01-01T00%3A00%3A00Z&se=2024-01-01T01%3A00%3A00Z&sr=b&sp=r&sig=c2lnbmF0dXJl",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(presigned_url.provider(), Some(Provider::AzureBlob));
+    /// assert_eq!(presigned_url.permission(), Some(Permission::Read));
+    /// assert_eq!(presigned_url.valid_from(), UNIX_EPOCH + Duration::from_secs(1704067200));
+    /// assert_eq!(presigned_url.valid_until(), UNIX_EPOCH + Duration::from_secs(1704070800));
+    /// assert_eq!(
+    ///     presigned_url.object_uri(),
+    ///     Some("abfss://container@account.dfs.core.windows.net/file.csv")
+    /// );
+    /// ```
+    pub fn parse(url: &str) -> Result<Self, SignerError> {
+        crate::parse::parse_presigned_url(url)
+    }
+
+    /// Add headers that a client must send along with a request to the
+    /// presigned URL.
     ///
     /// # Example
     /// ```rust
@@ -63,13 +131,116 @@ impl PresignedUrl {
     ///     "https://my_bucket.s3.eu-west-1.amazonaws.com/my_key",
     ///     SystemTime::now(),
     ///     Duration::from_secs(60),
+    /// )
+    /// .with_required_headers([("x-amz-server-side-encryption", "aws:kms")]);
+    /// assert_eq!(
+    ///     presigned_url.required_headers().get("x-amz-server-side-encryption"),
+    ///     Some(&"aws:kms".to_string())
     /// );
-    /// assert_eq!(presigned_url.url(), "https://my_bucket.s3.eu-west-1.amazonaws.com/my_key");
     /// ```
+    #[must_use]
+    pub fn with_required_headers<K, V>(mut self, headers: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.required_headers
+            .extend(headers.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
+    /// Record the provider that issued the URL.
+    #[must_use]
+    pub fn with_provider(mut self, provider: Provider) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    /// Record the permission the URL grants.
+    #[must_use]
+    pub fn with_permission(mut self, permission: Permission) -> Self {
+        self.permission = Some(permission);
+        self
+    }
+
+    /// Record the cloud native URI of the object the URL refers to, for
+    /// example `s3://bucket/key`.
+    #[must_use]
+    pub fn with_object_uri(mut self, object_uri: impl Into<String>) -> Self {
+        self.object_uri = Some(object_uri.into());
+        self
+    }
+
+    /// Return the presigned URL as a string, including its signature.
+    ///
+    /// Deprecated because the name does not make it obvious that the
+    /// signature is included. Use [`PresignedUrl::expose_url`] instead.
+    #[deprecated(note = "use `expose_url`, which makes exposing the signature explicit")]
     pub fn url(&self) -> &str {
         &self.url
     }
 
+    /// Return the full presigned URL, including its signature.
+    ///
+    /// Anyone holding the URL can use it until it expires, so it should not
+    /// end up in logs or error messages. The `Debug` and `Display`
+    /// representations of a `PresignedUrl` redact the signature instead.
+    ///
+    /// # Example
+    /// ```rust
+    /// use std::time::{Duration, SystemTime};
+    /// use cloud_file_signer::PresignedUrl;
+    ///
+    /// let presigned_url = PresignedUrl::new(
+    ///     "https://account.blob.core.windows.net/container/file.csv?sp=r&sig=c2lnbmF0dXJl",
+    ///     SystemTime::now(),
+    ///     Duration::from_secs(60),
+    /// );
+    /// assert_eq!(
+    ///     presigned_url.expose_url(),
+    ///     "https://account.blob.core.windows.net/container/file.csv?sp=r&sig=c2lnbmF0dXJl"
+    /// );
+    /// assert_eq!(
+    ///     presigned_url.to_string(),
+    ///     "https://account.blob.core.windows.net/container/file.csv?sp=r&sig=REDACTED"
+    /// );
+    /// ```
+    pub fn expose_url(&self) -> &str {
+        &self.url
+    }
+
+    /// Return the headers that a client must send along with a request to
+    /// the presigned URL, for example server-side encryption headers that
+    /// are part of the signature. Header names are lower case.
+    pub fn required_headers(&self) -> &BTreeMap<String, String> {
+        &self.required_headers
+    }
+
+    /// Return the provider that issued the URL, if known.
+    pub fn provider(&self) -> Option<Provider> {
+        self.provider
+    }
+
+    /// Return the permission the URL grants, if known.
+    pub fn permission(&self) -> Option<Permission> {
+        self.permission
+    }
+
+    /// Return the HTTP method with which the URL is used, if known: `GET`
+    /// for read and `PUT` for write permission.
+    pub fn method(&self) -> Option<&'static str> {
+        self.permission.map(|permission| match permission {
+            Permission::Read => "GET",
+            Permission::Write => "PUT",
+        })
+    }
+
+    /// Return the cloud native URI of the object the URL refers to, if
+    /// known. For CDN URLs this is the URL without the signature.
+    pub fn object_uri(&self) -> Option<&str> {
+        self.object_uri.as_deref()
+    }
+
     /// Return the time at which the URL became valid.
     ///
     /// # Example
@@ -170,18 +341,333 @@ impl PresignedUrl {
 
 impl Display for PresignedUrl {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.url)
+        f.write_str(&redact_url(&self.url))
     }
 }
 
+impl Debug for PresignedUrl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PresignedUrl")
+            .field("url", &redact_url(&self.url))
+            .field("valid_from", &self.valid_from)
+            .field("valid_for_duration", &self.valid_for_duration)
+            .field("required_headers", &redact_headers(&self.required_headers))
+            .field("provider", &self.provider)
+            .field("permission", &self.permission)
+            .field("object_uri", &self.object_uri)
+            .finish()
+    }
+}
+
+/// Query parameters that carry a signature or a session token.
+const SECRET_PARAMS: [&str; 5] = [
+    "X-Amz-Signature",
+    "X-Amz-Security-Token",
+    "X-Goog-Signature",
+    "Signature",
+    "sig",
+];
+
+/// Replace the values of the signature and session token query parameters
+/// of a signed URL with `REDACTED`.
+pub(crate) fn redact_url(url: &str) -> Cow<'_, str> {
+    let Some((base, query)) = url.split_once('?') else {
+        return Cow::Borrowed(url);
+    };
+    let is_secret = |pair: &str| {
+        let name = pair.split_once('=').map_or(pair, |(name, _)| name);
+        SECRET_PARAMS
+            .iter()
+            .any(|param| param.eq_ignore_ascii_case(name))
+    };
+    if !query.split('&').any(is_secret) {
+        return Cow::Borrowed(url);
+    }
+    let query = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if is_secret(name) => Cow::Owned(format!("{name}=REDACTED")),
+            _ => Cow::Borrowed(pair),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    Cow::Owned(format!("{base}?{query}"))
+}
+
+/// Replace the values of the headers that carry a customer provided
+/// encryption key (SSE-C), or its digest, with `REDACTED`.
+fn redact_headers(headers: &BTreeMap<String, String>) -> BTreeMap<&str, &str> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let lowercase = name.to_ascii_lowercase();
+            let value = if lowercase.ends_with("-customer-key")
+                || lowercase.ends_with("-customer-key-md5")
+            {
+                "REDACTED"
+            } else {
+                value.as_str()
+            };
+            (name.as_str(), value)
+        })
+        .collect()
+}
+
 impl AsRef<str> for PresignedUrl {
     fn as_ref(&self) -> &str {
         &self.url
     }
 }
 
+/// Return the full presigned URL, including its signature, like
+/// [`PresignedUrl::expose_url`]. Use the `Display` representation for a
+/// redacted URL.
 impl From<PresignedUrl> for String {
     fn from(url: PresignedUrl) -> Self {
         url.as_ref().to_string()
     }
 }
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for PresignedUrl {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct SerializedUrl<'a> {
+            url: &'a str,
+            valid_from: String,
+            valid_until: String,
+            valid_from_millis: i64,
+            valid_until_millis: i64,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            provider: Option<Provider>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            permission: Option<Permission>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            method: Option<&'static str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            object_uri: Option<&'a str>,
+            #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+            required_headers: &'a BTreeMap<String, String>,
+        }
+
+        let valid_until = self.valid_until();
+        SerializedUrl {
+            url: &self.url,
+            valid_from: rfc3339(self.valid_from),
+            valid_until: rfc3339(valid_until),
+            valid_from_millis: epoch_millis(self.valid_from),
+            valid_until_millis: epoch_millis(valid_until),
+            provider: self.provider,
+            permission: self.permission,
+            method: self.method(),
+            object_uri: self.object_uri(),
+            required_headers: &self.required_headers,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PresignedUrl {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(serde::Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct DeserializedUrl {
+            url: String,
+            valid_from: Option<String>,
+            valid_until: Option<String>,
+            valid_from_millis: Option<i64>,
+            valid_until_millis: Option<i64>,
+            provider: Option<Provider>,
+            permission: Option<Permission>,
+            object_uri: Option<String>,
+            #[serde(default)]
+            required_headers: BTreeMap<String, String>,
+        }
+
+        let url = DeserializedUrl::deserialize(deserializer)?;
+        let valid_from = timestamp("validFrom", url.valid_from, url.valid_from_millis)?;
+        let valid_until = timestamp("validUntil", url.valid_until, url.valid_until_millis)?;
+        let valid_for_duration = valid_until
+            .duration_since(valid_from)
+            .map_err(|_| D::Error::custom("`validUntil` is before `validFrom`"))?;
+        Ok(Self {
+            url: url.url,
+            valid_from,
+            valid_for_duration,
+            required_headers: url.required_headers,
+            provider: url.provider,
+            permission: url.permission,
+            object_uri: url.object_uri,
+        })
+    }
+}
+
+/// Read a timestamp from its RFC 3339 form, or else from its milliseconds
+/// since the Unix epoch.
+#[cfg(feature = "serde")]
+fn timestamp<E: serde::de::Error>(
+    name: &'static str,
+    rfc3339: Option<String>,
+    millis: Option<i64>,
+) -> Result<SystemTime, E> {
+    match (rfc3339, millis) {
+        (Some(time), _) => chrono::DateTime::parse_from_rfc3339(&time)
+            .map(SystemTime::from)
+            .map_err(|e| E::custom(format!("invalid `{name}`: {e}"))),
+        (None, Some(millis)) => u64::try_from(millis)
+            .map(|millis| SystemTime::UNIX_EPOCH + Duration::from_millis(millis))
+            .map_err(|e| E::custom(format!("invalid `{name}Millis`: {e}"))),
+        (None, None) => Err(E::missing_field(name)),
+    }
+}
+
+#[cfg(feature = "serde")]
+fn rfc3339(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)
+}
+
+#[cfg(feature = "serde")]
+fn epoch_millis(time: SystemTime) -> i64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_millis()).unwrap_or(i64::MAX))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    #[test]
+    fn redact_signatures() {
+        for (url, redacted) in [
+            (
+                "https://bucket.s3.amazonaws.com/key?X-Amz-Date=20240101T000000Z&X-Amz-Signature=abc&X-Amz-Security-Token=token",
+                "https://bucket.s3.amazonaws.com/key?X-Amz-Date=20240101T000000Z&X-Amz-Signature=REDACTED&X-Amz-Security-Token=REDACTED",
+            ),
+            (
+                "https://account.blob.core.windows.net/c/b?sv=2022-11-02&sig=c2ln%3D",
+                "https://account.blob.core.windows.net/c/b?sv=2022-11-02&sig=REDACTED",
+            ),
+            (
+                "https://storage.googleapis.com/bucket/key?X-Goog-Signature=00&X-Goog-Expires=60",
+                "https://storage.googleapis.com/bucket/key?X-Goog-Signature=REDACTED&X-Goog-Expires=60",
+            ),
+            (
+                "https://cdn.example.com/a.jpg?Expires=1&KeyName=k&Signature=c2ln",
+                "https://cdn.example.com/a.jpg?Expires=1&KeyName=k&Signature=REDACTED",
+            ),
+            (
+                "https://example.com/file?signature_version=2",
+                "https://example.com/file?signature_version=2",
+            ),
+            ("s3://bucket/key", "s3://bucket/key"),
+        ] {
+            assert_eq!(redact_url(url), redacted);
+        }
+    }
+
+    #[test]
+    fn debug_and_display_hide_signature() {
+        let presigned_url = PresignedUrl::new(
+            "https://bucket.s3.amazonaws.com/key?X-Amz-Signature=0123abcd",
+            UNIX_EPOCH,
+            Duration::from_secs(60),
+        );
+        assert!(!format!("{presigned_url:?}").contains("0123abcd"));
+        assert!(!presigned_url.to_string().contains("0123abcd"));
+        assert!(presigned_url.expose_url().contains("0123abcd"));
+    }
+
+    #[test]
+    fn redact_customer_key_headers() {
+        let presigned_url = PresignedUrl::new(
+            "https://bucket.s3.amazonaws.com/key?X-Amz-Signature=0123abcd",
+            UNIX_EPOCH,
+            Duration::from_secs(60),
+        )
+        .with_required_headers([
+            ("x-amz-server-side-encryption-customer-algorithm", "AES256"),
+            ("x-amz-server-side-encryption-customer-key", "a2V5LWJ5dGVz"),
+            (
+                "x-amz-server-side-encryption-customer-key-MD5",
+                "bWQ1LWJ5dGVz",
+            ),
+        ]);
+        let redacted = redact_headers(presigned_url.required_headers());
+        assert_eq!(
+            redacted.into_values().collect::<Vec<_>>(),
+            ["AES256", "REDACTED", "REDACTED"]
+        );
+        let debug = format!("{presigned_url:?}");
+        assert!(debug.contains("AES256"));
+        assert!(!debug.contains("a2V5LWJ5dGVz"));
+        assert!(!debug.contains("bWQ1LWJ5dGVz"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trips() {
+        let presigned_url = PresignedUrl::new(
+            "https://bucket.s3.amazonaws.com/key?X-Amz-Signature=abc",
+            UNIX_EPOCH + Duration::from_secs(1704067200),
+            Duration::from_millis(3_600_500),
+        )
+        .with_provider(Provider::S3)
+        .with_permission(Permission::Write)
+        .with_object_uri("s3://bucket/key")
+        .with_required_headers([("x-amz-server-side-encryption", "AES256")]);
+
+        let json = serde_json::to_value(&presigned_url).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "url": "https://bucket.s3.amazonaws.com/key?X-Amz-Signature=abc",
+                "validFrom": "2024-01-01T00:00:00Z",
+                "validUntil": "2024-01-01T01:00:00.500Z",
+                "validFromMillis": 1704067200000i64,
+                "validUntilMillis": 1704070800500i64,
+                "provider": "s3",
+                "permission": "write",
+                "method": "PUT",
+                "objectUri": "s3://bucket/key",
+                "requiredHeaders": {"x-amz-server-side-encryption": "AES256"},
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<PresignedUrl>(json).unwrap(),
+            presigned_url
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_minimal_representation() {
+        let presigned_url = PresignedUrl::new(
+            "https://example.com/file",
+            UNIX_EPOCH + Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+        let json = serde_json::to_value(&presigned_url).unwrap();
+        assert_eq!(json.as_object().unwrap().len(), 5);
+
+        let from_millis = serde_json::from_value::<PresignedUrl>(serde_json::json!({
+            "url": "https://example.com/file",
+            "validFromMillis": 60000,
+            "validUntilMillis": 120000,
+        }))
+        .unwrap();
+        assert_eq!(from_millis, presigned_url);
+
+        let reversed = serde_json::from_value::<PresignedUrl>(serde_json::json!({
+            "url": "https://example.com/file",
+            "validFrom": "2024-01-01T01:00:00Z",
+            "validUntil": "2024-01-01T00:00:00+00:00",
+        }));
+        assert!(reversed.is_err());
+    }
+}
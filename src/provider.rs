@@ -0,0 +1,105 @@
+//! The services that issue signed URLs.
+
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use crate::error::SignerError;
+
+/// A service that issues signed URLs.
+///
+/// With the `serde` feature, providers are represented by their
+/// [`as_str`](Provider::as_str) name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Provider {
+    /// Amazon S3, and S3 compatible object stores.
+    #[cfg_attr(feature = "serde", serde(rename = "s3"))]
+    S3,
+    /// Azure Blob Storage.
+    #[cfg_attr(feature = "serde", serde(rename = "azure-blob"))]
+    AzureBlob,
+    /// Google Cloud Storage.
+    #[cfg_attr(feature = "serde", serde(rename = "gcs"))]
+    Gcs,
+    /// Amazon CloudFront.
+    #[cfg_attr(feature = "serde", serde(rename = "cloudfront"))]
+    CloudFront,
+    /// Google Cloud CDN.
+    #[cfg_attr(feature = "serde", serde(rename = "cloud-cdn"))]
+    CloudCdn,
+}
+
+impl Provider {
+    /// Return the name of the provider, as used in its `Display`
+    /// representation.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Provider::S3 => "s3",
+            Provider::AzureBlob => "azure-blob",
+            Provider::Gcs => "gcs",
+            Provider::CloudFront => "cloudfront",
+            Provider::CloudCdn => "cloud-cdn",
+        }
+    }
+}
+
+impl Display for Provider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Provider {
+    type Err = SignerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "s3" => Ok(Self::S3),
+            "azure-blob" => Ok(Self::AzureBlob),
+            "gcs" => Ok(Self::Gcs),
+            "cloudfront" => Ok(Self::CloudFront),
+            "cloud-cdn" => Ok(Self::CloudCdn),
+            u => Err(SignerError::other_error(format!(
+                "`{u}` is not recognized as a provider of signed URLs."
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display_round_trips() {
+        for provider in [
+            Provider::S3,
+            Provider::AzureBlob,
+            Provider::Gcs,
+            Provider::CloudFront,
+            Provider::CloudCdn,
+        ] {
+            assert_eq!(provider.to_string().parse::<Provider>().unwrap(), provider);
+        }
+        assert!("dropbox".parse::<Provider>().is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_matches_display() {
+        for provider in [
+            Provider::S3,
+            Provider::AzureBlob,
+            Provider::Gcs,
+            Provider::CloudFront,
+            Provider::CloudCdn,
+        ] {
+            let json = serde_json::to_string(&provider).unwrap();
+            assert_eq!(json, format!("\"{provider}\""));
+            assert_eq!(serde_json::from_str::<Provider>(&json).unwrap(), provider);
+        }
+    }
+}
@@ -0,0 +1,454 @@
+//! A wrapper signer that retries transient failures.
+
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime};
+
+use crate::{CloudFileSigner, Permission, PresignedUrl, SignOptions, SignerError};
+
+/// The policy with which a [`RetryingSigner`] retries failed requests.
+///
+/// The delay before retry `n` (counting from zero) is
+/// `initial_backoff * 2^n`, capped at `max_backoff`. With jitter enabled,
+/// which is the default, the actual delay is drawn uniformly between zero
+/// and that value, so that many clients that failed at the same time do not
+/// retry in lockstep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+    timeout: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            jitter: true,
+            timeout: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create a new policy that retries up to three times, with backoff
+    /// starting at 100 milliseconds and capped at 5 seconds, with jitter and
+    /// without a timeout.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of retries after the first attempt.
+    #[must_use]
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Set the delay before the first retry.
+    #[must_use]
+    pub fn with_initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Set the maximum delay between two attempts.
+    #[must_use]
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Enable or disable jitter on the delay between attempts.
+    #[must_use]
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Abort an attempt that takes longer than `timeout`. An attempt that
+    /// times out fails with a retryable `Network` error.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Return the maximum number of retries after the first attempt.
+    #[must_use]
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// Return the delay before the first retry.
+    #[must_use]
+    pub fn initial_backoff(&self) -> Duration {
+        self.initial_backoff
+    }
+
+    /// Return the maximum delay between two attempts.
+    #[must_use]
+    pub fn max_backoff(&self) -> Duration {
+        self.max_backoff
+    }
+
+    /// Return `true` if jitter is applied to the delay between attempts.
+    #[must_use]
+    pub fn jitter(&self) -> bool {
+        self.jitter
+    }
+
+    /// Return the timeout of a single attempt, if any.
+    #[must_use]
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Return the delay before retry `retry`, counting from zero, without
+    /// jitter.
+    fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.checked_pow(retry).unwrap_or(u32::MAX);
+        self.initial_backoff
+            .checked_mul(factor)
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff))
+    }
+
+    fn delay(&self, retry: u32) -> Duration {
+        let backoff = self.backoff(retry);
+        if self.jitter {
+            backoff.mul_f64(random_fraction())
+        } else {
+            backoff
+        }
+    }
+}
+
+/// Return a random number in `[0, 1)`. The randomly keyed hasher of the
+/// standard library is good enough for jitter.
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// A signer that retries requests that failed with a transient error.
+///
+/// Signing can involve network calls, for example to sign a blob with the
+/// IAM API when no private key is available, to fetch an Azure user
+/// delegation key, or to refresh credentials. A request that fails with an
+/// error for which [`SignerError::is_retryable`] returns `true` is retried
+/// according to the [`RetryPolicy`]. Other errors are returned immediately.
+///
+/// [`CloudFileSigner::sign_many`] forwards the whole batch to the wrapped
+/// signer, then retries the paths that failed with a retryable error as a
+/// smaller batch, so the wrapped signer can still share work across paths.
+///
+/// Retries wait with [`tokio::time::sleep`], so the signer has to be used
+/// within a Tokio runtime.
+///
+/// # Example
+/// ```rust
+/// use std::time::Duration;
+/// use cloud_file_signer::{RetryPolicy, RetryingSigner};
+/// use cloud_file_signer::gcp::CloudCdnSigner;
+///
+/// let cdn_signer = CloudCdnSigner::new("my-key", "https://cdn.example.com", b"secret".to_vec());
+/// let signer = RetryingSigner::new(cdn_signer).with_policy(
+///     RetryPolicy::new()
+///         .with_max_retries(5)
+///         .with_timeout(Duration::from_secs(2)),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryingSigner<S> {
+    inner: S,
+    policy: RetryPolicy,
+}
+
+impl<S> RetryingSigner<S> {
+    /// Wrap `inner` so that transient failures are retried with the default
+    /// [`RetryPolicy`].
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            policy: RetryPolicy::default(),
+        }
+    }
+
+    /// Set the policy with which failed requests are retried.
+    #[must_use]
+    pub fn with_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Return the policy with which failed requests are retried.
+    #[must_use]
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    /// Return a reference to the wrapped signer.
+    #[must_use]
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Consume the wrapper and return the wrapped signer.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    async fn retry<'a, F, Fut>(&'a self, attempt: F) -> Result<PresignedUrl, SignerError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<PresignedUrl, SignerError>> + 'a,
+    {
+        let mut retry = 0;
+        loop {
+            let result = self
+                .run(attempt())
+                .await
+                .unwrap_or_else(|| Err(self.timeout_error()));
+            match result {
+                Err(e) if e.is_retryable() && retry < self.policy.max_retries => {
+                    tokio::time::sleep(self.policy.delay(retry)).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Run an attempt, or return `None` if it does not complete within the
+    /// timeout of the policy.
+    async fn run<T>(&self, attempt: impl Future<Output = T>) -> Option<T> {
+        match self.policy.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, attempt).await.ok(),
+            None => Some(attempt.await),
+        }
+    }
+
+    fn timeout_error(&self) -> SignerError {
+        SignerError::network_error(format!(
+            "Signing did not complete within {} ms",
+            self.policy.timeout.unwrap_or_default().as_millis()
+        ))
+    }
+}
+
+impl<S: CloudFileSigner> RetryingSigner<S> {
+    /// Sign `paths` with one call to the wrapped signer.
+    async fn sign_batch(
+        &self,
+        paths: &[&str],
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Vec<Result<PresignedUrl, SignerError>> {
+        self.run(
+            self.inner
+                .sign_many(paths, valid_from, expires_in, permission),
+        )
+        .await
+        .unwrap_or_else(|| paths.iter().map(|_| Err(self.timeout_error())).collect())
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: CloudFileSigner> CloudFileSigner for RetryingSigner<S> {
+    async fn sign(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Result<PresignedUrl, SignerError> {
+        self.retry(|| self.inner.sign(path, valid_from, expires_in, permission))
+            .await
+    }
+
+    async fn sign_with_options(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+        options: &SignOptions,
+    ) -> Result<PresignedUrl, SignerError> {
+        self.retry(|| {
+            self.inner
+                .sign_with_options(path, valid_from, expires_in, permission, options)
+        })
+        .await
+    }
+
+    async fn sign_many(
+        &self,
+        paths: &[&str],
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Vec<Result<PresignedUrl, SignerError>> {
+        let mut results = self
+            .sign_batch(paths, valid_from, expires_in, permission)
+            .await;
+        let mut retry = 0;
+        loop {
+            let failed = results
+                .iter()
+                .enumerate()
+                .filter(|(_, result)| result.as_ref().is_err_and(SignerError::is_retryable))
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+            if failed.is_empty() || retry >= self.policy.max_retries {
+                return results;
+            }
+            tokio::time::sleep(self.policy.delay(retry)).await;
+            retry += 1;
+
+            let failed_paths = failed.iter().map(|&i| paths[i]).collect::<Vec<_>>();
+            let retried = self
+                .sign_batch(&failed_paths, valid_from, expires_in, permission)
+                .await;
+            for (i, result) in failed.into_iter().zip(retried) {
+                results[i] = result;
+            }
+        }
+    }
+
+    fn sign_many_concurrency(&self) -> usize {
+        self.inner.sign_many_concurrency()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::{BatchSigner, FailingSigner, MockSigner, SlowSigner};
+    use crate::SignerErrorKind;
+
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy::new().with_initial_backoff(Duration::from_millis(1))
+    }
+
+    #[tokio::test]
+    async fn retries_retryable_errors() {
+        let signer = RetryingSigner::new(
+            FailingSigner::new(MockSigner).fail_times(2, SignerError::throttled("Slow down")),
+        )
+        .with_policy(fast_policy());
+        let presigned_url = signer
+            .sign("s3://bucket/key", SystemTime::now(), HOUR, Permission::Read)
+            .await
+            .unwrap();
+        assert!(presigned_url.expose_url().starts_with("s3://bucket/key?"));
+        assert_eq!(signer.inner().call_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let signer = RetryingSigner::new(
+            FailingSigner::new(MockSigner)
+                .fail_times(10, SignerError::network_error("Connection reset")),
+        )
+        .with_policy(fast_policy().with_max_retries(2));
+        let err = signer
+            .sign("s3://bucket/key", SystemTime::now(), HOUR, Permission::Read)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::Network);
+        assert_eq!(signer.inner().call_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_other_errors() {
+        let signer = RetryingSigner::new(
+            FailingSigner::new(MockSigner)
+                .then_fail(SignerError::credentials_error("Expired token")),
+        )
+        .with_policy(fast_policy());
+        let err = signer
+            .sign("s3://bucket/key", SystemTime::now(), HOUR, Permission::Read)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::CredentialsError);
+        assert_eq!(signer.inner().call_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn times_out_slow_attempts() {
+        let inner = FailingSigner::new(SlowSigner::default().with_delay(Duration::from_secs(10)));
+        let signer = RetryingSigner::new(inner).with_policy(
+            fast_policy()
+                .with_max_retries(1)
+                .with_timeout(Duration::from_millis(10)),
+        );
+        let err = signer
+            .sign("s3://bucket/key", SystemTime::now(), HOUR, Permission::Read)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::Network);
+        assert_eq!(signer.inner().call_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn sign_many_retries_failed_paths_as_a_batch() {
+        let signer = RetryingSigner::new(BatchSigner::new(
+            FailingSigner::new(MockSigner)
+                .fail_times(1, SignerError::throttled("Slow down"))
+                .then_fail(SignerError::credentials_error("Expired token")),
+        ))
+        .with_policy(fast_policy());
+        let results = signer
+            .sign_many(
+                &["s3://bucket/a", "s3://bucket/b", "s3://bucket/c"],
+                SystemTime::now(),
+                HOUR,
+                Permission::Read,
+            )
+            .await;
+
+        // Only the throttled path is retried.
+        assert_eq!(signer.inner().batches(), vec![3, 1]);
+        assert!(results[0]
+            .as_ref()
+            .unwrap()
+            .expose_url()
+            .starts_with("s3://bucket/a?"));
+        assert_eq!(
+            results[1].as_ref().unwrap_err().kind(),
+            SignerErrorKind::CredentialsError
+        );
+        assert!(results[2].is_ok());
+    }
+
+    #[test]
+    fn exponential_backoff_is_capped() {
+        let policy = RetryPolicy::new()
+            .with_initial_backoff(Duration::from_millis(100))
+            .with_max_backoff(Duration::from_secs(1))
+            .with_jitter(false);
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(1), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(800));
+        assert_eq!(policy.delay(4), Duration::from_secs(1));
+        assert_eq!(policy.delay(100), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn jitter_stays_below_backoff() {
+        let policy = RetryPolicy::new();
+        for retry in 0..10 {
+            assert!(policy.delay(retry) <= policy.backoff(retry));
+        }
+    }
+}
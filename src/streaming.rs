@@ -0,0 +1,188 @@
+//! Signing of streams of paths.
+
+use std::time::{Duration, SystemTime};
+
+use futures::stream::{BoxStream, Stream, StreamExt};
+
+use crate::{CloudFileSigner, Permission, PresignedUrl, SignerError};
+
+/// Extension methods for every [`CloudFileSigner`], including boxed and
+/// shared signers.
+pub trait CloudFileSignerExt: CloudFileSigner {
+    /// Sign a stream of paths. Every URL is valid for the specified
+    /// duration and grants the specified permission.
+    ///
+    /// The returned stream yields every path together with the result of
+    /// signing it, in the order of the input stream. At most `parallelism`
+    /// paths are signed concurrently, and paths are only pulled from the
+    /// input stream when there is room to sign them, so a slow consumer
+    /// slows down the input as well. A `parallelism` of zero is treated as
+    /// one.
+    ///
+    /// # Example
+    /// ```rust
+    /// # async fn example() {
+    /// use std::time::{Duration, SystemTime};
+    /// use futures::{stream, StreamExt};
+    /// use cloud_file_signer::{CloudFileSigner, CloudFileSignerExt, Permission};
+    /// use cloud_file_signer::gcp::CloudCdnSigner;
+    ///
+    /// let signer: Box<dyn CloudFileSigner> = Box::new(CloudCdnSigner::new(
+    ///     "my-key",
+    ///     "https://cdn.example.com",
+    ///     b"secret".to_vec(),
+    /// ));
+    /// let paths = stream::iter((0..1000).map(|i| format!("images/{i}.jpg")));
+    /// let mut signed = signer.sign_stream(
+    ///     paths,
+    ///     SystemTime::now(),
+    ///     Duration::from_secs(3600),
+    ///     Permission::Read,
+    ///     16,
+    /// );
+    /// while let Some((path, result)) = signed.next().await {
+    ///     println!("{path}: {}", result.unwrap());
+    /// }
+    /// # }
+    /// ```
+    fn sign_stream<'a, P>(
+        &'a self,
+        paths: P,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+        parallelism: usize,
+    ) -> BoxStream<'a, (String, Result<PresignedUrl, SignerError>)>
+    where
+        P: Stream<Item = String> + Send + 'a,
+    {
+        paths
+            .map(move |path| async move {
+                let result = self.sign(&path, valid_from, expires_in, permission).await;
+                (path, result)
+            })
+            .buffered(parallelism.max(1))
+            .boxed()
+    }
+}
+
+impl<T: CloudFileSigner + ?Sized> CloudFileSignerExt for T {}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use futures::stream;
+
+    use crate::testing::SlowSigner;
+
+    use super::*;
+
+    fn paths(n: usize) -> impl Stream<Item = String> {
+        stream::iter((0..n).map(|i| format!("s3://bucket/{i}")))
+    }
+
+    #[tokio::test]
+    async fn keeps_input_order() {
+        let signer = SlowSigner::default();
+        let signed = signer
+            .sign_stream(
+                paths(50),
+                SystemTime::now(),
+                Duration::from_secs(60),
+                Permission::Read,
+                8,
+            )
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(signed.len(), 50);
+        for (i, (path, result)) in signed.into_iter().enumerate() {
+            assert_eq!(path, format!("s3://bucket/{i}"));
+            assert!(result
+                .unwrap()
+                .expose_url()
+                .starts_with(&format!("{path}?")));
+        }
+    }
+
+    #[tokio::test]
+    async fn bounds_parallelism() {
+        let signer = SlowSigner::default();
+        signer
+            .sign_stream(
+                paths(50),
+                SystemTime::now(),
+                Duration::from_secs(60),
+                Permission::Read,
+                4,
+            )
+            .for_each(|_| async {})
+            .await;
+        let max_in_flight = signer.max_in_flight();
+        assert!(max_in_flight > 1);
+        assert!(max_in_flight <= 4);
+    }
+
+    #[tokio::test]
+    async fn pulls_paths_on_demand() {
+        let pulled = AtomicUsize::new(0);
+        let signer = SlowSigner::default();
+        let paths = paths(1000).inspect(|_| {
+            pulled.fetch_add(1, Ordering::SeqCst);
+        });
+        let first = signer
+            .sign_stream(
+                paths,
+                SystemTime::now(),
+                Duration::from_secs(60),
+                Permission::Read,
+                4,
+            )
+            .take(1)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(first.len(), 1);
+        assert!(pulled.load(Ordering::SeqCst) <= 4);
+    }
+
+    #[tokio::test]
+    async fn reports_errors_per_path() {
+        let signer = SlowSigner::default();
+        let paths = stream::iter(vec!["s3://bucket/a".to_string(), String::new()]);
+        let signed = signer
+            .sign_stream(
+                paths,
+                SystemTime::now(),
+                Duration::from_secs(60),
+                Permission::Read,
+                0,
+            )
+            .collect::<Vec<_>>()
+            .await;
+        assert!(signed[0].1.is_ok());
+        assert!(signed[1].1.is_err());
+    }
+
+    async fn sign_three<S: CloudFileSigner + ?Sized>(signer: &S) -> usize {
+        signer
+            .sign_stream(
+                paths(3),
+                SystemTime::now(),
+                Duration::from_secs(60),
+                Permission::Read,
+                2,
+            )
+            .count()
+            .await
+    }
+
+    #[tokio::test]
+    async fn works_for_boxed_and_shared_signers() {
+        let boxed: Box<dyn CloudFileSigner> = Box::new(SlowSigner::default());
+        let shared: Arc<dyn CloudFileSigner> = Arc::new(SlowSigner::default());
+        assert_eq!(sign_three(&boxed).await, 3);
+        assert_eq!(sign_three(&shared).await, 3);
+        assert_eq!(sign_three(boxed.as_ref()).await, 3);
+    }
+}
@@ -0,0 +1,193 @@
+//! Tracing spans and metrics for signing requests.
+//!
+//! Every request to a provider signer runs in a `sign_url` span with the
+//! fields `provider`, `bucket` (the bucket or container), `permission` and
+//! `expires_in_secs`. When the request completes, the span records its
+//! `outcome` (`ok` or `error`) and, for failures, the `error_kind`. Paths,
+//! signed URLs and credentials are never recorded.
+//!
+//! With the `metrics` feature, every request also emits:
+//!
+//! - `cloud_file_signer_sign_requests_total`, a counter labelled with
+//!   `provider` and `outcome`,
+//! - `cloud_file_signer_sign_errors_total`, a counter labelled with
+//!   `provider` and `error_kind`,
+//! - `cloud_file_signer_sign_duration_seconds`, a histogram labelled with
+//!   `provider` and `outcome`.
+
+use std::future::Future;
+use std::time::Duration;
+
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
+
+use crate::{Permission, PresignedUrl, Provider, SignerError};
+
+/// Run a signing request in a `sign_url` span, and record its outcome.
+pub(crate) async fn instrument_sign<F>(
+    provider: Provider,
+    permission: Permission,
+    expires_in: Duration,
+    sign: F,
+) -> Result<PresignedUrl, SignerError>
+where
+    F: Future<Output = Result<PresignedUrl, SignerError>>,
+{
+    let span = tracing::info_span!(
+        "sign_url",
+        provider = provider.as_str(),
+        bucket = Empty,
+        permission = %permission,
+        expires_in_secs = expires_in.as_secs(),
+        outcome = Empty,
+        error_kind = Empty,
+    );
+    #[cfg(feature = "metrics")]
+    let start = std::time::Instant::now();
+    let result = sign.instrument(span.clone()).await;
+
+    match &result {
+        Ok(_) => {
+            span.record("outcome", "ok");
+        }
+        Err(e) => {
+            span.record("outcome", "error");
+            span.record("error_kind", tracing::field::display(e.kind()));
+            tracing::debug!(parent: &span, error_kind = %e.kind(), "signing failed");
+        }
+    }
+    #[cfg(feature = "metrics")]
+    record_metrics(provider, &result, start.elapsed());
+    result
+}
+
+/// Record the bucket or container of the current signing request.
+pub(crate) fn record_bucket(bucket: &str) {
+    Span::current().record("bucket", bucket);
+}
+
+#[cfg(feature = "metrics")]
+fn record_metrics(
+    provider: Provider,
+    result: &Result<PresignedUrl, SignerError>,
+    elapsed: Duration,
+) {
+    let outcome = if result.is_ok() { "ok" } else { "error" };
+    metrics::counter!(
+        "cloud_file_signer_sign_requests_total",
+        "provider" => provider.as_str(),
+        "outcome" => outcome,
+    )
+    .increment(1);
+    metrics::histogram!(
+        "cloud_file_signer_sign_duration_seconds",
+        "provider" => provider.as_str(),
+        "outcome" => outcome,
+    )
+    .record(elapsed.as_secs_f64());
+    if let Err(e) = result {
+        metrics::counter!(
+            "cloud_file_signer_sign_errors_total",
+            "provider" => provider.as_str(),
+            "error_kind" => e.kind().to_string(),
+        )
+        .increment(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    fn sign(result: Result<PresignedUrl, SignerError>) -> Result<PresignedUrl, SignerError> {
+        futures::executor::block_on(instrument_sign(
+            Provider::S3,
+            Permission::Read,
+            Duration::from_secs(60),
+            async move {
+                record_bucket("bucket");
+                result
+            },
+        ))
+    }
+
+    fn presigned_url() -> PresignedUrl {
+        PresignedUrl::new(
+            "s3://bucket/key?signed",
+            UNIX_EPOCH,
+            Duration::from_secs(60),
+        )
+    }
+
+    #[test]
+    fn result_is_passed_through() {
+        assert_eq!(sign(Ok(presigned_url())).unwrap(), presigned_url());
+        let err = SignerError::throttled("Slow down");
+        assert_eq!(sign(Err(err.clone())).unwrap_err(), err);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn metrics_per_provider_and_error_kind() {
+        use std::sync::Mutex;
+
+        use metrics::{
+            Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+        };
+
+        #[derive(Default)]
+        struct KeyRecorder(Mutex<Vec<String>>);
+
+        impl KeyRecorder {
+            fn register(&self, key: &Key) {
+                let labels = key
+                    .labels()
+                    .map(|label| format!("{}={}", label.key(), label.value()))
+                    .collect::<Vec<_>>();
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("{}{{{}}}", key.name(), labels.join(",")));
+            }
+        }
+
+        impl Recorder for KeyRecorder {
+            fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+            fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+                self.register(key);
+                Counter::noop()
+            }
+
+            fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+                self.register(key);
+                Gauge::noop()
+            }
+
+            fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+                self.register(key);
+                Histogram::noop()
+            }
+        }
+
+        let recorder = KeyRecorder::default();
+        metrics::with_local_recorder(&recorder, || {
+            sign(Ok(presigned_url())).unwrap();
+            sign(Err(SignerError::credentials_error("expired"))).unwrap_err();
+        });
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [
+                "cloud_file_signer_sign_requests_total{provider=s3,outcome=ok}",
+                "cloud_file_signer_sign_duration_seconds{provider=s3,outcome=ok}",
+                "cloud_file_signer_sign_requests_total{provider=s3,outcome=error}",
+                "cloud_file_signer_sign_duration_seconds{provider=s3,outcome=error}",
+                "cloud_file_signer_sign_errors_total{provider=s3,error_kind=CREDENTIALS_ERROR}",
+            ]
+        );
+    }
+}
@@ -0,0 +1,661 @@
+//! Signers for testing code that depends on [`CloudFileSigner`].
+//!
+//! These signers do not talk to a cloud provider, so code that signs URLs
+//! can be unit tested without credentials, localstack or Azurite. The
+//! module is available with the `testing` feature, which is typically
+//! enabled for dev-dependencies only:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! cloud-file-signer = { version = "0.1", features = ["testing"] }
+//! ```
+//!
+//! # Example
+//! ```rust
+//! # futures::executor::block_on(async {
+//! use std::time::{Duration, UNIX_EPOCH};
+//! use cloud_file_signer::{CloudFileSigner, Permission};
+//! use cloud_file_signer::testing::{MockSigner, RecordingSigner};
+//!
+//! let signer = RecordingSigner::new(MockSigner::new());
+//! let presigned_url = signer
+//!     .sign("s3://bucket/key", UNIX_EPOCH, Duration::from_secs(60), Permission::Read)
+//!     .await
+//!     .unwrap();
+//! assert_eq!(
+//!     presigned_url.expose_url(),
+//!     "s3://bucket/key?permission=read&valid_from=0&expires_in=60"
+//! );
+//! assert_eq!(signer.calls()[0].path(), "s3://bucket/key");
+//! # });
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
+use crate::{CloudFileSigner, Permission, PresignedUrl, SignOptions, SignerError};
+
+/// A signer that produces predictable URLs without signing anything.
+///
+/// The URL is the path with the permission, the start of the validity
+/// window in seconds since the Unix epoch and the duration in seconds
+/// appended as query parameters, for example
+/// `s3://bucket/key?permission=read&valid_from=1704067200&expires_in=3600`.
+/// A version id or snapshot from the [`SignOptions`] is appended as
+/// `version_id` or `snapshot`. Server-side encryption options are accepted
+/// and ignored.
+///
+/// Paths without a scheme, such as `bucket/key`, are rejected with a
+/// `CloudUriParseError`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct MockSigner;
+
+impl MockSigner {
+    /// Create a new mock signer.
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait::async_trait]
+impl CloudFileSigner for MockSigner {
+    async fn sign(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Result<PresignedUrl, SignerError> {
+        self.sign_with_options(
+            path,
+            valid_from,
+            expires_in,
+            permission,
+            &SignOptions::default(),
+        )
+        .await
+    }
+
+    async fn sign_with_options(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+        options: &SignOptions,
+    ) -> Result<PresignedUrl, SignerError> {
+        if !path.contains("://") {
+            return Err(SignerError::uri_parse_error(format!(
+                "`{path}` is not a cloud storage URI"
+            )));
+        }
+        let permission = match permission {
+            Permission::Read => "read",
+            Permission::Write => "write",
+        };
+        let valid_from_secs = match valid_from.duration_since(UNIX_EPOCH) {
+            Ok(since_epoch) => since_epoch.as_secs().to_string(),
+            Err(before_epoch) => format!("-{}", before_epoch.duration().as_secs()),
+        };
+        let mut url = format!(
+            "{path}?permission={permission}&valid_from={valid_from_secs}&expires_in={}",
+            expires_in.as_secs()
+        );
+        if let Some(version_id) = options.version_id() {
+            url.push_str("&version_id=");
+            url.extend(utf8_percent_encode(version_id, NON_ALPHANUMERIC));
+        }
+        if let Some(snapshot) = options.snapshot() {
+            url.push_str("&snapshot=");
+            url.extend(utf8_percent_encode(snapshot, NON_ALPHANUMERIC));
+        }
+        Ok(PresignedUrl::new(url, valid_from, expires_in))
+    }
+}
+
+/// A call to a [`RecordingSigner`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SignCall {
+    path: String,
+    valid_from: SystemTime,
+    expires_in: Duration,
+    permission: Permission,
+    options: SignOptions,
+}
+
+impl SignCall {
+    /// Return the path that was signed.
+    #[must_use]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Return the requested start of the validity window.
+    #[must_use]
+    pub fn valid_from(&self) -> SystemTime {
+        self.valid_from
+    }
+
+    /// Return the requested duration of the validity window.
+    #[must_use]
+    pub fn expires_in(&self) -> Duration {
+        self.expires_in
+    }
+
+    /// Return the requested permission.
+    #[must_use]
+    pub fn permission(&self) -> Permission {
+        self.permission
+    }
+
+    /// Return the requested sign options. Calls to
+    /// [`CloudFileSigner::sign`] are recorded with empty options.
+    #[must_use]
+    pub fn options(&self) -> &SignOptions {
+        &self.options
+    }
+}
+
+/// A signer that records every call before forwarding it to the wrapped
+/// signer.
+///
+/// Calls are recorded in the order in which they are made, whether the
+/// wrapped signer succeeds or not. Calls to [`CloudFileSigner::sign_many`]
+/// are recorded as one call per path.
+#[derive(Debug, Default)]
+pub struct RecordingSigner<S = MockSigner> {
+    inner: S,
+    calls: Mutex<Vec<SignCall>>,
+}
+
+impl<S> RecordingSigner<S> {
+    /// Wrap `inner` so that calls to it are recorded.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Return the recorded calls.
+    #[must_use]
+    pub fn calls(&self) -> Vec<SignCall> {
+        self.lock().clone()
+    }
+
+    /// Return the number of recorded calls.
+    #[must_use]
+    pub fn call_count(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Forget all recorded calls.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Return a reference to the wrapped signer.
+    #[must_use]
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Consume the wrapper and return the wrapped signer.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<SignCall>> {
+        self.calls.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn record(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+        options: &SignOptions,
+    ) {
+        self.lock().push(SignCall {
+            path: path.to_string(),
+            valid_from,
+            expires_in,
+            permission,
+            options: options.clone(),
+        });
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: CloudFileSigner> CloudFileSigner for RecordingSigner<S> {
+    async fn sign(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Result<PresignedUrl, SignerError> {
+        self.record(
+            path,
+            valid_from,
+            expires_in,
+            permission,
+            &SignOptions::default(),
+        );
+        self.inner
+            .sign(path, valid_from, expires_in, permission)
+            .await
+    }
+
+    async fn sign_with_options(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+        options: &SignOptions,
+    ) -> Result<PresignedUrl, SignerError> {
+        self.record(path, valid_from, expires_in, permission, options);
+        self.inner
+            .sign_with_options(path, valid_from, expires_in, permission, options)
+            .await
+    }
+
+    fn sign_many_concurrency(&self) -> usize {
+        self.inner.sign_many_concurrency()
+    }
+}
+
+/// A signer that fails with configurable errors.
+///
+/// Errors are queued with [`fail_times`](Self::fail_times) and
+/// [`then_fail`](Self::then_fail), and returned one per call in the order in
+/// which they were queued. Once the queue is empty, calls are forwarded to
+/// the wrapped signer. A signer created with [`always`](Self::always) fails
+/// every call.
+///
+/// # Example
+/// ```rust
+/// # futures::executor::block_on(async {
+/// use std::time::{Duration, SystemTime};
+/// use cloud_file_signer::{CloudFileSigner, Permission, SignerError};
+/// use cloud_file_signer::testing::{FailingSigner, MockSigner};
+///
+/// let signer = FailingSigner::new(MockSigner::new())
+///     .fail_times(2, SignerError::throttled("Slow down"));
+/// let sign = || signer.sign("s3://bucket/key", SystemTime::now(), Duration::from_secs(60), Permission::Read);
+/// assert!(sign().await.is_err());
+/// assert!(sign().await.is_err());
+/// assert!(sign().await.is_ok());
+/// assert_eq!(signer.call_count(), 3);
+/// # });
+/// ```
+#[derive(Debug, Default)]
+pub struct FailingSigner<S = MockSigner> {
+    inner: S,
+    errors: Mutex<VecDeque<SignerError>>,
+    permanent: Option<SignerError>,
+    calls: AtomicUsize,
+}
+
+impl FailingSigner<MockSigner> {
+    /// Create a signer that fails every call with `error`.
+    #[must_use]
+    pub fn always(error: SignerError) -> Self {
+        Self {
+            permanent: Some(error),
+            ..Self::new(MockSigner)
+        }
+    }
+}
+
+impl<S> FailingSigner<S> {
+    /// Wrap `inner` with an empty queue of errors.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            errors: Mutex::new(VecDeque::new()),
+            permanent: None,
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    /// Queue `error` to be returned by the next `times` calls.
+    #[must_use]
+    pub fn fail_times(self, times: usize, error: SignerError) -> Self {
+        let mut errors = self.lock();
+        for _ in 0..times {
+            errors.push_back(error.clone());
+        }
+        drop(errors);
+        self
+    }
+
+    /// Queue `error` to be returned by one call.
+    #[must_use]
+    pub fn then_fail(self, error: SignerError) -> Self {
+        self.fail_times(1, error)
+    }
+
+    /// Return the number of calls made, including the failed ones.
+    #[must_use]
+    pub fn call_count(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    /// Return a reference to the wrapped signer.
+    #[must_use]
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<SignerError>> {
+        self.errors.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn next_error(&self) -> Option<SignerError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        match &self.permanent {
+            Some(error) => Some(error.clone()),
+            None => self.lock().pop_front(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: CloudFileSigner> CloudFileSigner for FailingSigner<S> {
+    async fn sign(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Result<PresignedUrl, SignerError> {
+        if let Some(error) = self.next_error() {
+            return Err(error);
+        }
+        self.inner
+            .sign(path, valid_from, expires_in, permission)
+            .await
+    }
+
+    async fn sign_with_options(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+        options: &SignOptions,
+    ) -> Result<PresignedUrl, SignerError> {
+        if let Some(error) = self.next_error() {
+            return Err(error);
+        }
+        self.inner
+            .sign_with_options(path, valid_from, expires_in, permission, options)
+            .await
+    }
+
+    fn sign_many_concurrency(&self) -> usize {
+        self.inner.sign_many_concurrency()
+    }
+}
+
+/// A signer that sleeps before forwarding each call to the wrapped signer
+/// and tracks the number of concurrent calls.
+///
+/// Only [`CloudFileSigner::sign`] is forwarded, so sign options are handled
+/// by the default implementation of the trait.
+#[cfg(test)]
+#[derive(Debug)]
+pub(crate) struct SlowSigner<S = MockSigner> {
+    inner: S,
+    delay: Duration,
+    concurrency: Option<usize>,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+#[cfg(test)]
+impl Default for SlowSigner {
+    fn default() -> Self {
+        Self::new(MockSigner)
+    }
+}
+
+#[cfg(test)]
+impl<S> SlowSigner<S> {
+    /// Wrap `inner` so that every call takes at least a millisecond.
+    pub(crate) fn new(inner: S) -> Self {
+        Self {
+            inner,
+            delay: Duration::from_millis(1),
+            concurrency: None,
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    /// Set how long every call sleeps.
+    #[must_use]
+    pub(crate) fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Override the concurrency limit of the wrapped signer.
+    #[must_use]
+    pub(crate) fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = Some(concurrency);
+        self
+    }
+
+    /// Return the largest number of calls that were in flight at once.
+    pub(crate) fn max_in_flight(&self) -> usize {
+        self.max_in_flight.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl<S: CloudFileSigner> CloudFileSigner for SlowSigner<S> {
+    async fn sign(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Result<PresignedUrl, SignerError> {
+        let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+        tokio::time::sleep(self.delay).await;
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        self.inner
+            .sign(path, valid_from, expires_in, permission)
+            .await
+    }
+
+    fn sign_many_concurrency(&self) -> usize {
+        self.concurrency
+            .unwrap_or_else(|| self.inner.sign_many_concurrency())
+    }
+}
+
+/// A signer that overrides [`CloudFileSigner::sign_many`] and records the
+/// number of paths in every batch, to check that wrappers forward batches.
+///
+/// Every path of a batch is signed with [`CloudFileSigner::sign`] of the
+/// wrapped signer, one after the other.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct BatchSigner<S = MockSigner> {
+    inner: S,
+    batches: Mutex<Vec<usize>>,
+}
+
+#[cfg(test)]
+impl<S> BatchSigner<S> {
+    /// Wrap `inner` so that batches are recorded.
+    pub(crate) fn new(inner: S) -> Self {
+        Self {
+            inner,
+            batches: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Return the number of paths in every call to `sign_many`.
+    pub(crate) fn batches(&self) -> Vec<usize> {
+        self.batches
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl<S: CloudFileSigner> CloudFileSigner for BatchSigner<S> {
+    async fn sign(
+        &self,
+        path: &str,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Result<PresignedUrl, SignerError> {
+        self.inner
+            .sign(path, valid_from, expires_in, permission)
+            .await
+    }
+
+    async fn sign_many(
+        &self,
+        paths: &[&str],
+        valid_from: SystemTime,
+        expires_in: Duration,
+        permission: Permission,
+    ) -> Vec<Result<PresignedUrl, SignerError>> {
+        self.batches
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(paths.len());
+        let mut results = Vec::with_capacity(paths.len());
+        for path in paths {
+            results.push(
+                self.inner
+                    .sign(path, valid_from, expires_in, permission)
+                    .await,
+            );
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{RetryPolicy, RetryingSigner, SignerErrorKind};
+
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn epoch(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[tokio::test]
+    async fn mock_urls_are_deterministic() {
+        let presigned_url = MockSigner::new()
+            .sign_with_options(
+                "gs://bucket/key",
+                epoch(1704067200),
+                MINUTE,
+                Permission::Write,
+                &SignOptions::new().with_version_id("v 1"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            presigned_url.expose_url(),
+            "gs://bucket/key?permission=write&valid_from=1704067200&expires_in=60&version_id=v%201"
+        );
+        assert_eq!(presigned_url.valid_from(), epoch(1704067200));
+        assert_eq!(presigned_url.valid_until(), epoch(1704067260));
+    }
+
+    #[tokio::test]
+    async fn mock_rejects_relative_paths() {
+        let err = MockSigner::new()
+            .sign("bucket/key", epoch(0), MINUTE, Permission::Read)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), SignerErrorKind::CloudUriParseError);
+    }
+
+    #[tokio::test]
+    async fn records_calls() {
+        let signer = RecordingSigner::new(MockSigner::new());
+        let paths = ["s3://bucket/a", "not a uri"];
+        let results = signer
+            .sign_many(&paths, epoch(0), MINUTE, Permission::Read)
+            .await;
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+
+        let mut calls = signer.calls();
+        calls.sort_by(|a, b| a.path().cmp(b.path()));
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1].path(), "s3://bucket/a");
+        assert_eq!(calls[1].permission(), Permission::Read);
+        assert_eq!(calls[1].expires_in(), MINUTE);
+        assert!(calls[1].options().is_empty());
+
+        signer.clear();
+        assert_eq!(signer.call_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn failing_signer_recovers() {
+        let signer = FailingSigner::new(MockSigner::new())
+            .fail_times(1, SignerError::throttled("Slow down"))
+            .then_fail(SignerError::credentials_error("Expired"));
+        let sign = || signer.sign("s3://bucket/key", epoch(0), MINUTE, Permission::Read);
+        assert_eq!(sign().await.unwrap_err().kind(), SignerErrorKind::Throttled);
+        assert_eq!(
+            sign().await.unwrap_err().kind(),
+            SignerErrorKind::CredentialsError
+        );
+        assert!(sign().await.is_ok());
+        assert_eq!(signer.call_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn always_failing_signer() {
+        let signer = FailingSigner::always(SignerError::network_error("Unreachable"));
+        for _ in 0..3 {
+            let err = signer
+                .sign("s3://bucket/key", epoch(0), MINUTE, Permission::Read)
+                .await
+                .unwrap_err();
+            assert_eq!(err.kind(), SignerErrorKind::Network);
+        }
+    }
+
+    #[tokio::test]
+    async fn composes_with_retrying_signer() {
+        let signer = RetryingSigner::new(
+            FailingSigner::new(MockSigner::new())
+                .fail_times(2, SignerError::network_error("Connection reset")),
+        )
+        .with_policy(RetryPolicy::new().with_initial_backoff(Duration::from_millis(1)));
+        signer
+            .sign("s3://bucket/key", epoch(0), MINUTE, Permission::Read)
+            .await
+            .unwrap();
+        assert_eq!(signer.inner().call_count(), 3);
+    }
+}
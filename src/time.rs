@@ -0,0 +1,156 @@
+//! Helpers for signing times: converting them to Unix timestamps and
+//! aligning them to fixed buckets.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::SignerError;
+
+/// Return `time` as whole seconds since the Unix epoch, the format of the
+/// expiry and start times in CloudFront and Cloud CDN signatures.
+pub(crate) fn epoch_seconds(time: SystemTime) -> Result<u64, SignerError> {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .map_err(|_| SignerError::signing_error("Signed URLs cannot use times before 1970"))
+}
+
+/// Round `time` down to the start of the bucket it falls in. Buckets are
+/// aligned to the Unix epoch. A zero bucket leaves `time` unchanged.
+pub(crate) fn floor_to_bucket(time: SystemTime, bucket: Duration) -> SystemTime {
+    let bucket_nanos = bucket.as_nanos();
+    if bucket_nanos == 0 {
+        return time;
+    }
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => {
+            let remainder = since_epoch.as_nanos() % bucket_nanos;
+            time - nanos_to_duration(remainder)
+        }
+        Err(before_epoch) => {
+            let remainder = before_epoch.duration().as_nanos() % bucket_nanos;
+            if remainder == 0 {
+                time
+            } else {
+                time - nanos_to_duration(bucket_nanos - remainder)
+            }
+        }
+    }
+}
+
+/// Round `time` up to the end of the bucket it falls in. Buckets are aligned
+/// to the Unix epoch. A time on a bucket boundary, or a zero bucket, leaves
+/// `time` unchanged.
+pub(crate) fn ceil_to_bucket(time: SystemTime, bucket: Duration) -> SystemTime {
+    let floor = floor_to_bucket(time, bucket);
+    if floor == time {
+        time
+    } else {
+        floor + bucket
+    }
+}
+
+/// Quantize a validity window to buckets. The start is rounded down and the
+/// end is rounded up, so the quantized window always covers the requested
+/// window. Returns the quantized start and duration.
+pub(crate) fn quantize_window(
+    valid_from: SystemTime,
+    expires_in: Duration,
+    bucket: Duration,
+) -> (SystemTime, Duration) {
+    let start = floor_to_bucket(valid_from, bucket);
+    let end = ceil_to_bucket(valid_from + expires_in, bucket);
+    let duration = end.duration_since(start).unwrap_or(expires_in);
+    (start, duration)
+}
+
+fn nanos_to_duration(nanos: u128) -> Duration {
+    // The remainder is smaller than the bucket, which is a valid duration.
+    Duration::new(
+        (nanos / 1_000_000_000) as u64,
+        (nanos % 1_000_000_000) as u32,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn epoch(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn epoch_seconds_truncates() {
+        assert_eq!(
+            epoch_seconds(epoch(1704067200) + Duration::from_millis(999)).unwrap(),
+            1704067200
+        );
+        assert!(epoch_seconds(UNIX_EPOCH - MINUTE).is_err());
+    }
+
+    #[test]
+    fn floor_rounds_down() {
+        let bucket = 15 * MINUTE;
+        assert_eq!(
+            floor_to_bucket(epoch(1704067200), bucket),
+            epoch(1704067200)
+        );
+        assert_eq!(
+            floor_to_bucket(epoch(1704067201), bucket),
+            epoch(1704067200)
+        );
+        assert_eq!(
+            floor_to_bucket(epoch(1704067200) + Duration::from_nanos(1), bucket),
+            epoch(1704067200)
+        );
+        assert_eq!(
+            floor_to_bucket(epoch(1704068099), bucket),
+            epoch(1704067200)
+        );
+        assert_eq!(
+            floor_to_bucket(epoch(1704068100), bucket),
+            epoch(1704068100)
+        );
+    }
+
+    #[test]
+    fn ceil_rounds_up() {
+        let bucket = 15 * MINUTE;
+        assert_eq!(ceil_to_bucket(epoch(1704067200), bucket), epoch(1704067200));
+        assert_eq!(ceil_to_bucket(epoch(1704067201), bucket), epoch(1704068100));
+    }
+
+    #[test]
+    fn zero_bucket_is_identity() {
+        let time = epoch(1704067201) + Duration::from_nanos(17);
+        assert_eq!(floor_to_bucket(time, Duration::ZERO), time);
+        assert_eq!(ceil_to_bucket(time, Duration::ZERO), time);
+    }
+
+    #[test]
+    fn floor_before_epoch() {
+        let time = UNIX_EPOCH - Duration::from_secs(30);
+        assert_eq!(floor_to_bucket(time, MINUTE), UNIX_EPOCH - MINUTE);
+        assert_eq!(
+            floor_to_bucket(UNIX_EPOCH - MINUTE, MINUTE),
+            UNIX_EPOCH - MINUTE
+        );
+    }
+
+    #[test]
+    fn quantized_window_covers_request() {
+        let bucket = 15 * MINUTE;
+        let (start, duration) = quantize_window(epoch(1704067620), 60 * MINUTE, bucket);
+        assert_eq!(start, epoch(1704067200));
+        assert_eq!(start + duration, epoch(1704071700));
+    }
+
+    #[test]
+    fn quantized_window_is_stable_within_bucket() {
+        let bucket = 15 * MINUTE;
+        let first = quantize_window(epoch(1704067260), 60 * MINUTE, bucket);
+        let second = quantize_window(epoch(1704067980), 60 * MINUTE, bucket);
+        assert_eq!(first, second);
+    }
+}
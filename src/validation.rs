@@ -0,0 +1,261 @@
+//! Validation of the validity window of a signed URL.
+
+use std::time::{Duration, SystemTime};
+
+use crate::SignerError;
+
+/// One week, the longest validity window of most providers.
+pub(crate) const ONE_WEEK: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The limits a cloud provider places on the validity window of a signed
+/// URL.
+///
+/// Signers check the requested window against these limits before calling
+/// into the provider's SDK, so an invalid window always fails with the same
+/// [`SignerErrorKind`](crate::SignerErrorKind):
+///
+/// - an expiration of zero fails with `InvalidExpiration`,
+/// - an expiration above [`max_expiration`](Self::max_expiration) fails with
+///   `ExpirationTooLong`,
+/// - a start time more than [`max_start_delay`](Self::max_start_delay) in
+///   the future, or a window that has already ended, fails with
+///   `InvalidStartTime`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ValidityLimits {
+    provider: &'static str,
+    max_expiration: Duration,
+    max_start_delay: Duration,
+}
+
+impl ValidityLimits {
+    /// Create a new set of limits for the named provider.
+    #[must_use]
+    pub const fn new(
+        provider: &'static str,
+        max_expiration: Duration,
+        max_start_delay: Duration,
+    ) -> Self {
+        Self {
+            provider,
+            max_expiration,
+            max_start_delay,
+        }
+    }
+
+    /// Return the name of the provider the limits apply to.
+    #[must_use]
+    pub const fn provider(&self) -> &'static str {
+        self.provider
+    }
+
+    /// Return the longest duration a signed URL can be valid for.
+    #[must_use]
+    pub const fn max_expiration(&self) -> Duration {
+        self.max_expiration
+    }
+
+    /// Return how far in the future the validity window of a signed URL can
+    /// start.
+    #[must_use]
+    pub const fn max_start_delay(&self) -> Duration {
+        self.max_start_delay
+    }
+
+    /// Check that a URL valid from `valid_from` for `expires_in` respects
+    /// the limits.
+    pub fn validate(
+        &self,
+        valid_from: SystemTime,
+        expires_in: Duration,
+    ) -> Result<(), SignerError> {
+        self.validate_at(valid_from, expires_in, SystemTime::now())
+    }
+
+    /// Check that a URL valid from `valid_from` for `expires_in` respects
+    /// the limits at time `now`.
+    pub fn validate_at(
+        &self,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        now: SystemTime,
+    ) -> Result<(), SignerError> {
+        if expires_in.is_zero() {
+            return Err(SignerError::invalid_expiration(format!(
+                "{} signed URLs must be valid for longer than zero seconds.",
+                self.provider
+            )));
+        }
+        if expires_in > self.max_expiration {
+            return Err(SignerError::expiration_too_long(format!(
+                "{} signed URLs cannot be valid for longer than {} seconds. Requested: {} seconds",
+                self.provider,
+                self.max_expiration.as_secs(),
+                expires_in.as_secs()
+            )));
+        }
+        if valid_from > now + self.max_start_delay {
+            return Err(SignerError::invalid_start_time(format!(
+                "{} signed URLs cannot become valid more than {} seconds in the future.",
+                self.provider,
+                self.max_start_delay.as_secs()
+            )));
+        }
+        if valid_from + expires_in <= now {
+            return Err(SignerError::invalid_start_time(format!(
+                "{} signed URL would already have expired. The validity window must end in the future.",
+                self.provider
+            )));
+        }
+        Ok(())
+    }
+
+    /// Backdate the start of a validity window by up to `clock_skew`, so
+    /// the signed URL is accepted by clients and frontends whose clocks are
+    /// behind. The end of the window stays the same, and the backdated
+    /// window never exceeds the maximum expiration.
+    pub(crate) fn backdate(
+        &self,
+        valid_from: SystemTime,
+        expires_in: Duration,
+        clock_skew: Duration,
+    ) -> SigningWindow {
+        let backdate = clock_skew.min(self.max_expiration.saturating_sub(expires_in));
+        match valid_from.checked_sub(backdate) {
+            Some(signed_from) => SigningWindow {
+                valid_from,
+                expires_in,
+                signed_from,
+                signed_expires_in: expires_in + backdate,
+            },
+            None => SigningWindow {
+                valid_from,
+                expires_in,
+                signed_from: valid_from,
+                signed_expires_in: expires_in,
+            },
+        }
+    }
+}
+
+/// A validity window as requested, and as it is signed after backdating the
+/// start for clock skew.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SigningWindow {
+    /// The requested start of the window, reported in the signed URL.
+    pub(crate) valid_from: SystemTime,
+    /// The requested duration of the window, reported in the signed URL.
+    pub(crate) expires_in: Duration,
+    /// The start of the window that is signed.
+    pub(crate) signed_from: SystemTime,
+    /// The duration of the window that is signed.
+    pub(crate) signed_expires_in: Duration,
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::UNIX_EPOCH;
+
+    use crate::SignerErrorKind;
+
+    use super::*;
+
+    const LIMITS: ValidityLimits = ValidityLimits::new("Test", ONE_WEEK, ONE_WEEK);
+
+    fn now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1704067200)
+    }
+
+    fn kind(valid_from: SystemTime, expires_in: Duration) -> Option<SignerErrorKind> {
+        LIMITS
+            .validate_at(valid_from, expires_in, now())
+            .err()
+            .map(|e| e.kind())
+    }
+
+    #[test]
+    fn valid_windows() {
+        assert_eq!(kind(now(), Duration::from_secs(3600)), None);
+        assert_eq!(kind(now(), ONE_WEEK), None);
+        assert_eq!(kind(now() + ONE_WEEK, Duration::from_secs(1)), None);
+        assert_eq!(
+            kind(now() - Duration::from_secs(60), Duration::from_secs(61)),
+            None
+        );
+    }
+
+    #[test]
+    fn zero_expiration() {
+        assert_eq!(
+            kind(now(), Duration::ZERO),
+            Some(SignerErrorKind::InvalidExpiration)
+        );
+    }
+
+    #[test]
+    fn expiration_too_long() {
+        assert_eq!(
+            kind(now(), ONE_WEEK + Duration::from_secs(1)),
+            Some(SignerErrorKind::ExpirationTooLong)
+        );
+    }
+
+    #[test]
+    fn start_too_far_in_future() {
+        assert_eq!(
+            kind(
+                now() + ONE_WEEK + Duration::from_secs(1),
+                Duration::from_secs(60)
+            ),
+            Some(SignerErrorKind::InvalidStartTime)
+        );
+    }
+
+    #[test]
+    fn window_already_ended() {
+        assert_eq!(
+            kind(now() - Duration::from_secs(60), Duration::from_secs(60)),
+            Some(SignerErrorKind::InvalidStartTime)
+        );
+        assert_eq!(
+            kind(now() - ONE_WEEK, Duration::from_secs(3600)),
+            Some(SignerErrorKind::InvalidStartTime)
+        );
+    }
+
+    #[test]
+    fn backdate_keeps_end_of_window() {
+        let skew = Duration::from_secs(30);
+        let window = LIMITS.backdate(now(), Duration::from_secs(3600), skew);
+        assert_eq!(window.valid_from, now());
+        assert_eq!(window.expires_in, Duration::from_secs(3600));
+        assert_eq!(window.signed_from, now() - skew);
+        assert_eq!(
+            window.signed_from + window.signed_expires_in,
+            now() + Duration::from_secs(3600)
+        );
+    }
+
+    #[test]
+    fn backdate_respects_max_expiration() {
+        let window = LIMITS.backdate(
+            now(),
+            ONE_WEEK - Duration::from_secs(10),
+            Duration::from_secs(30),
+        );
+        assert_eq!(window.signed_from, now() - Duration::from_secs(10));
+        assert_eq!(window.signed_expires_in, ONE_WEEK);
+    }
+
+    #[test]
+    fn no_backdate_without_skew() {
+        let window = LIMITS.backdate(now(), Duration::from_secs(3600), Duration::ZERO);
+        assert_eq!(window.signed_from, now());
+        assert_eq!(window.signed_expires_in, Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn message_names_provider() {
+        let error = LIMITS.validate_at(now(), ONE_WEEK * 2, now()).unwrap_err();
+        assert!(error.message().starts_with("Test signed URLs"));
+    }
+}
@@ -0,0 +1,413 @@
+//! Offline verification of signed URLs.
+//!
+//! A verifier recomputes the signature of a signed URL from the same
+//! credentials that were used to sign it, without calling the cloud
+//! provider. This allows a gateway or proxy to validate signed URLs
+//! locally, and tests to check that a URL has not been tampered with.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+use hmac::{Hmac, Mac};
+use http::Uri;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use sha2::{Digest, Sha256};
+
+use crate::encoding::query_pairs;
+use crate::parse::{parse_url, parse_v4_date};
+use crate::validation::ONE_WEEK;
+use crate::{Permission, PresignedUrl, SignerError};
+
+/// A verifier that checks the signature of signed URLs offline.
+pub trait UrlVerifier {
+    /// Verify the signature of `presigned_url`.
+    ///
+    /// A URL whose signature does not match is reported as not authentic.
+    /// An error is returned if the URL is not a signed URL of the kind the
+    /// verifier understands, for example because a signature parameter is
+    /// missing.
+    ///
+    /// Headers that are part of the signature, other than `host`, are read
+    /// from [`PresignedUrl::required_headers`].
+    fn verify(&self, presigned_url: &PresignedUrl) -> Result<Verification, SignerError>;
+}
+
+impl<V: UrlVerifier + ?Sized> UrlVerifier for Box<V> {
+    fn verify(&self, presigned_url: &PresignedUrl) -> Result<Verification, SignerError> {
+        (**self).verify(presigned_url)
+    }
+}
+
+impl<V: UrlVerifier + ?Sized> UrlVerifier for std::sync::Arc<V> {
+    fn verify(&self, presigned_url: &PresignedUrl) -> Result<Verification, SignerError> {
+        (**self).verify(presigned_url)
+    }
+}
+
+/// The result of verifying a signed URL.
+///
+/// The resource and the validity window are read from the URL. They can
+/// only be trusted if the URL [is authentic](Self::is_authentic).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Verification {
+    authentic: bool,
+    permission: Option<Permission>,
+    resource: String,
+    valid_from: Option<SystemTime>,
+    valid_until: SystemTime,
+}
+
+impl Verification {
+    /// Create the result for a URL that is not authentic.
+    pub(crate) fn new(
+        resource: impl Into<String>,
+        valid_from: Option<SystemTime>,
+        valid_until: SystemTime,
+    ) -> Self {
+        Self {
+            authentic: false,
+            permission: None,
+            resource: resource.into(),
+            valid_from,
+            valid_until,
+        }
+    }
+
+    /// Mark the URL as authentic, granting `permission`.
+    pub(crate) fn authentic(mut self, permission: Option<Permission>) -> Self {
+        self.authentic = true;
+        self.permission = permission;
+        self
+    }
+
+    /// Return `true` if the signature of the URL matches the credentials of
+    /// the verifier.
+    #[must_use]
+    pub fn is_authentic(&self) -> bool {
+        self.authentic
+    }
+
+    /// Return the permission the URL grants, or `None` if the URL is not
+    /// authentic or grants neither read nor write access.
+    #[must_use]
+    pub fn permission(&self) -> Option<Permission> {
+        self.permission
+    }
+
+    /// Return the object, or for Azure container SAS tokens the container,
+    /// that the URL grants access to, as a cloud native URI such as
+    /// `s3://bucket/key`. URLs of unknown endpoints are returned without
+    /// their query string.
+    #[must_use]
+    pub fn resource(&self) -> &str {
+        &self.resource
+    }
+
+    /// Return the time at which the URL becomes valid, if the URL states
+    /// one.
+    #[must_use]
+    pub fn valid_from(&self) -> Option<SystemTime> {
+        self.valid_from
+    }
+
+    /// Return the time at which the URL expires.
+    #[must_use]
+    pub fn valid_until(&self) -> SystemTime {
+        self.valid_until
+    }
+
+    /// Return `true` if the URL has expired.
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(SystemTime::now())
+    }
+
+    /// Return `true` if the URL has expired at time `now`.
+    #[must_use]
+    pub fn is_expired_at(&self, now: SystemTime) -> bool {
+        now > self.valid_until
+    }
+
+    /// Return `true` if the URL is authentic and within its validity window.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.is_valid_at(SystemTime::now())
+    }
+
+    /// Return `true` if the URL is authentic and within its validity window
+    /// at time `now`.
+    #[must_use]
+    pub fn is_valid_at(&self, now: SystemTime) -> bool {
+        self.authentic
+            && self.valid_from.map_or(true, |valid_from| valid_from <= now)
+            && !self.is_expired_at(now)
+    }
+}
+
+/// Characters that are percent-encoded in the canonical query string of a
+/// V4 signature. Everything except the unreserved characters is encoded.
+const V4_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// The request methods a V4 signature is checked against, and the
+/// permission a signature for each method grants.
+const V4_METHODS: [(&str, Permission); 2] = [("GET", Permission::Read), ("PUT", Permission::Write)];
+
+/// A variant of the V4 query string signature, used by AWS SigV4 and by
+/// the Google Cloud Storage V4 HMAC signature.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct V4Scheme {
+    provider: &'static str,
+    algorithm: &'static str,
+    param_prefix: &'static str,
+    key_prefix: &'static str,
+    max_expires_in: Duration,
+}
+
+/// AWS Signature Version 4.
+pub(crate) const SIGV4: V4Scheme = V4Scheme {
+    provider: "AWS",
+    algorithm: "AWS4-HMAC-SHA256",
+    param_prefix: "X-Amz-",
+    key_prefix: "AWS4",
+    max_expires_in: ONE_WEEK,
+};
+
+/// The Google Cloud Storage V4 signature with an HMAC key.
+pub(crate) const GOOG4_HMAC: V4Scheme = V4Scheme {
+    provider: "Google Cloud Storage",
+    algorithm: "GOOG4-HMAC-SHA256",
+    param_prefix: "X-Goog-",
+    key_prefix: "GOOG4",
+    max_expires_in: ONE_WEEK,
+};
+
+impl V4Scheme {
+    /// Verify a V4 signed URL against an access key. The signature is
+    /// checked for a `GET` and a `PUT` request, which grant read and write
+    /// permission respectively.
+    ///
+    /// URLs that claim to be valid for longer than the provider allows are
+    /// rejected with `ExpirationTooLong`, since the provider would reject
+    /// them as well.
+    pub(crate) fn verify(
+        &self,
+        presigned_url: &PresignedUrl,
+        access_key_id: &str,
+        secret: &str,
+        resource: impl FnOnce(&Uri) -> String,
+    ) -> Result<Verification, SignerError> {
+        let uri = parse_url(presigned_url.expose_url())?;
+        let pairs = query_pairs(uri.query().unwrap_or_default())?;
+        let param = |name: &str| {
+            let name = format!("{}{name}", self.param_prefix);
+            pairs
+                .iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.as_str())
+                .ok_or_else(|| {
+                    SignerError::uri_parse_error(format!(
+                        "Not a {} signed URL: the `{name}` query parameter is missing.",
+                        self.provider
+                    ))
+                })
+        };
+
+        let algorithm = param("Algorithm")?;
+        if algorithm != self.algorithm {
+            return Err(SignerError::option_not_supported(format!(
+                "Only {} signatures can be verified. Received: `{algorithm}`.",
+                self.algorithm
+            )));
+        }
+        let (key_id, scope) = param("Credential")?.split_once('/').ok_or_else(|| {
+            SignerError::uri_parse_error("Invalid credential scope in signed URL.")
+        })?;
+        let date = param("Date")?;
+        let signed_from = parse_v4_date(date)?;
+        let expires_in = param("Expires")?
+            .parse::<u64>()
+            .map(Duration::from_secs)
+            .map_err(|e| {
+                SignerError::uri_parse_error(format!(
+                    "Invalid expiration in signed URL. Cause: {e}"
+                ))
+            })?;
+        if expires_in > self.max_expires_in {
+            return Err(SignerError::expiration_too_long(format!(
+                "{} signed URLs cannot be valid for longer than {} seconds. Received: {} seconds.",
+                self.provider,
+                self.max_expires_in.as_secs(),
+                expires_in.as_secs()
+            )));
+        }
+        let signed_until = signed_from.checked_add(expires_in).ok_or_else(|| {
+            SignerError::uri_parse_error("Invalid expiration in signed URL: out of range.")
+        })?;
+        let signature = decode_hex(param("Signature")?).ok_or_else(|| {
+            SignerError::uri_parse_error("Invalid signature in signed URL: not hexadecimal.")
+        })?;
+
+        let verification = Verification::new(resource(&uri), Some(signed_from), signed_until);
+        if key_id != access_key_id {
+            return Ok(verification);
+        }
+
+        let signature_param = format!("{}Signature", self.param_prefix);
+        let canonical_query = canonical_query(
+            pairs
+                .iter()
+                .filter(|(k, _)| *k != signature_param)
+                .map(|(k, v)| (k.as_str(), v.as_str())),
+        );
+        let signed_headers = param("SignedHeaders")?;
+        let canonical_headers =
+            canonical_headers(&uri, signed_headers, presigned_url.required_headers())?;
+        let path = match uri.path() {
+            "" => "/",
+            path => path,
+        };
+
+        let mut signing_key = format!("{}{secret}", self.key_prefix).into_bytes();
+        for part in scope.split('/') {
+            signing_key = hmac_sha256(&signing_key, part.as_bytes());
+        }
+
+        for (method, permission) in V4_METHODS {
+            let canonical_request = format!(
+                "{method}\n{path}\n{canonical_query}\n{canonical_headers}\n{signed_headers}\nUNSIGNED-PAYLOAD"
+            );
+            let string_to_sign = format!(
+                "{}\n{date}\n{scope}\n{}",
+                self.algorithm,
+                encode_hex(&Sha256::digest(canonical_request.as_bytes()))
+            );
+            if verify_hmac_sha256(&signing_key, string_to_sign.as_bytes(), &signature) {
+                return Ok(verification.authentic(Some(permission)));
+            }
+        }
+        Ok(verification)
+    }
+}
+
+/// Build the canonical query string of a V4 signature: names and values
+/// percent-encoded, sorted by name and then by value.
+pub(crate) fn canonical_query<'a>(pairs: impl Iterator<Item = (&'a str, &'a str)>) -> String {
+    let mut encoded = pairs
+        .map(|(k, v)| {
+            (
+                utf8_percent_encode(k, V4_ENCODE_SET).to_string(),
+                utf8_percent_encode(v, V4_ENCODE_SET).to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+    encoded.sort();
+    encoded
+        .into_iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Build the canonical headers of a V4 signature. The `host` header is taken
+/// from the URL, other headers from the required headers of the URL.
+fn canonical_headers(
+    uri: &Uri,
+    signed_headers: &str,
+    required_headers: &BTreeMap<String, String>,
+) -> Result<String, SignerError> {
+    signed_headers
+        .split(';')
+        .map(|name| {
+            let value = match name {
+                "host" => uri.authority().map(|a| a.as_str()),
+                _ => required_headers.get(name).map(String::as_str),
+            }
+            .ok_or_else(|| {
+                SignerError::signing_error(format!(
+                    "The `{name}` header is part of the signature, but its value is not known."
+                ))
+            })?;
+            let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+            Ok(format!("{name}:{value}\n"))
+        })
+        .collect()
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(value);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Check an HMAC-SHA256 signature in constant time.
+pub(crate) fn verify_hmac_sha256(key: &[u8], value: &[u8], signature: &[u8]) -> bool {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(value);
+    mac.verify_slice(signature).is_ok()
+}
+
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    fn epoch(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn validity_window() {
+        let verification =
+            Verification::new("s3://bucket/key", Some(epoch(100)), epoch(200)).authentic(None);
+        assert!(!verification.is_valid_at(epoch(99)));
+        assert!(verification.is_valid_at(epoch(100)));
+        assert!(verification.is_valid_at(epoch(200)));
+        assert!(!verification.is_valid_at(epoch(201)));
+        assert!(verification.is_expired_at(epoch(201)));
+    }
+
+    #[test]
+    fn inauthentic_urls_are_never_valid() {
+        let verification = Verification::new("s3://bucket/key", None, epoch(200));
+        assert!(!verification.is_authentic());
+        assert!(!verification.is_valid_at(epoch(100)));
+        assert_eq!(verification.permission(), None);
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        let bytes = [0x00, 0x7f, 0xab, 0xff];
+        assert_eq!(encode_hex(&bytes), "007fabff");
+        assert_eq!(decode_hex("007fabff").unwrap(), bytes);
+        assert_eq!(decode_hex("007").as_deref(), None);
+        assert_eq!(decode_hex("zz").as_deref(), None);
+    }
+
+    #[test]
+    fn canonical_query_is_sorted_and_encoded() {
+        let pairs = [("b", "x/y"), ("a-b", "1"), ("a", "1 2"), ("a", "0")];
+        assert_eq!(
+            canonical_query(pairs.into_iter()),
+            "a=0&a=1%202&a-b=1&b=x%2Fy"
+        );
+    }
+}
@@ -64,7 +64,55 @@ fn test_abfs_signer() {
         .unwrap();
 
     let c = reqwest::blocking::Client::builder().build().unwrap();
-    let res = c.get(presigned_url.url()).send().unwrap().bytes().unwrap();
+    let res = c
+        .get(presigned_url.expose_url())
+        .send()
+        .unwrap()
+        .bytes()
+        .unwrap();
 
     assert_eq!(res, "hello world");
 }
+
+#[test]
+fn test_abfs_signer_special_character_blobs() {
+    // abfss URIs are percent-decoded, so the blob names are encoded in the
+    // URIs.
+    let blobs = [
+        ("Q1 2024/report.csv", "Q1%202024/report.csv"),
+        ("naïve+data#1.csv", "na%C3%AFve%2Bdata%231.csv"),
+        ("100%/a&b=c?.txt", "100%25/a%26b%3Dc%3F.txt"),
+        (
+            "日本/ファイル.txt",
+            "%E6%97%A5%E6%9C%AC/%E3%83%95%E3%82%A1%E3%82%A4%E3%83%AB.txt",
+        ),
+    ];
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let container = format!("special-characters-{}", uuid::Uuid::new_v4());
+    let mock_abfs = MockAbfs::setup(&rt, container.clone());
+
+    let cb = ClientBuilder::emulator();
+    let signer = AbfsFileSigner::from_client_builder("devstoreaccount1", cb);
+    let c = reqwest::blocking::Client::builder().build().unwrap();
+
+    for (blob, encoded_blob) in blobs {
+        mock_abfs.put_blob(blob);
+        let uri =
+            format!("abfss://{container}@devstoreaccount1.dfs.core.windows.net/{encoded_blob}");
+        let presigned_url = rt
+            .block_on(signer.sign_read_only_starting_now(&uri, Duration::from_secs(3600)))
+            .unwrap();
+
+        let res = c
+            .get(presigned_url.expose_url())
+            .send()
+            .unwrap()
+            .bytes()
+            .unwrap();
+        assert_eq!(res, "hello world", "blob `{blob}`");
+    }
+}
@@ -1,14 +1,17 @@
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use aws_config::{BehaviorVersion, SdkConfig};
-use aws_sdk_s3::types::{Delete, ObjectIdentifier};
+use aws_sdk_s3::types::{
+    BucketVersioningStatus, Delete, ObjectIdentifier, VersioningConfiguration,
+};
 use aws_sdk_s3::{primitives::ByteStream, Client};
+use proptest::prelude::*;
 use reqwest::StatusCode;
 use tokio::runtime::Runtime;
 
 use cloud_file_signer::aws::AwsFileSigner;
-use cloud_file_signer::CloudFileSigner;
+use cloud_file_signer::{CloudFileSigner, Permission, ServerSideEncryption, SignOptions};
 
 struct MockS3<'a> {
     rt: &'a Runtime,
@@ -54,12 +57,48 @@ impl<'a> MockS3<'a> {
     }
 
     fn put_object(&self, key_name: &str) {
+        self.put_object_with_body(key_name, "hello world");
+    }
+
+    fn put_object_with_body(&self, key_name: &str, body: &'static str) -> Option<String> {
         let req = self
             .client()
             .put_object()
             .bucket(&self.bucket)
             .key(key_name)
-            .body(ByteStream::from_static("hello world".as_bytes()))
+            .body(ByteStream::from_static(body.as_bytes()))
+            .send();
+        self.rt
+            .block_on(req)
+            .unwrap()
+            .version_id()
+            .map(String::from)
+    }
+
+    fn server_side_encryption(&self, key_name: &str) -> Option<String> {
+        let req = self
+            .client()
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key_name)
+            .send();
+        self.rt
+            .block_on(req)
+            .unwrap()
+            .server_side_encryption()
+            .map(|sse| sse.as_str().to_string())
+    }
+
+    fn enable_versioning(&self) {
+        let req = self
+            .client()
+            .put_bucket_versioning()
+            .bucket(&self.bucket)
+            .versioning_configuration(
+                VersioningConfiguration::builder()
+                    .status(BucketVersioningStatus::Enabled)
+                    .build(),
+            )
             .send();
         self.rt.block_on(req).unwrap();
     }
@@ -67,15 +106,20 @@ impl<'a> MockS3<'a> {
 
 impl Drop for MockS3<'_> {
     fn drop(&mut self) {
-        let req = self.client().list_objects_v2().bucket(&self.bucket).send();
+        let req = self
+            .client()
+            .list_object_versions()
+            .bucket(&self.bucket)
+            .send();
         let obj = self.rt.block_on(req).unwrap();
         let objects_to_delete = obj
-            .contents()
+            .versions()
             .iter()
             .fold(Delete::builder(), |delete, obj| {
                 delete.objects(
                     ObjectIdentifier::builder()
                         .key(obj.key().unwrap())
+                        .set_version_id(obj.version_id().map(String::from))
                         .build()
                         .unwrap(),
                 )
@@ -113,7 +157,12 @@ fn test_s3_signer() {
         .unwrap();
 
     let c = reqwest::blocking::Client::builder().build().unwrap();
-    let res = c.get(presigned_url.url()).send().unwrap().bytes().unwrap();
+    let res = c
+        .get(presigned_url.expose_url())
+        .send()
+        .unwrap()
+        .bytes()
+        .unwrap();
     assert_eq!(res, "hello world");
 }
 
@@ -135,6 +184,199 @@ fn test_s3_signer_expired_url() {
 
     sleep(Duration::from_secs(5));
     let c = reqwest::blocking::Client::builder().build().unwrap();
-    let res = c.get(presigned_url.url()).send().unwrap().status();
+    let res = c.get(presigned_url.expose_url()).send().unwrap().status();
     assert_eq!(res, StatusCode::FORBIDDEN);
 }
+
+#[test]
+fn test_s3_signer_pinned_version() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let mock_s3 = MockS3::setup(&rt);
+    mock_s3.enable_versioning();
+
+    // Overwrite an object and read the first version through a pinned URL.
+    let first_version = mock_s3
+        .put_object_with_body("my-versioned-key", "first version")
+        .unwrap();
+    mock_s3.put_object_with_body("my-versioned-key", "second version");
+
+    let s3_url = format!("s3://{}/my-versioned-key", mock_s3.bucket());
+    let s3_signer = AwsFileSigner::new(mock_s3.client());
+    let options = SignOptions::new().with_version_id(&first_version);
+    let presigned_url = rt
+        .block_on(s3_signer.sign_with_options(
+            &s3_url,
+            SystemTime::now(),
+            Duration::from_secs(3600),
+            Permission::Read,
+            &options,
+        ))
+        .unwrap();
+
+    let c = reqwest::blocking::Client::builder().build().unwrap();
+    let res = c
+        .get(presigned_url.expose_url())
+        .send()
+        .unwrap()
+        .bytes()
+        .unwrap();
+    assert_eq!(res, "first version");
+
+    // The version can also be taken from an S3 URL.
+    let s3_url = format!(
+        "https://{}.s3.us-east-1.amazonaws.com/my-versioned-key?versionId={}",
+        mock_s3.bucket(),
+        first_version
+    );
+    let presigned_url = rt
+        .block_on(s3_signer.sign_read_only_starting_now(&s3_url, Duration::from_secs(3600)))
+        .unwrap();
+    let res = c
+        .get(presigned_url.expose_url())
+        .send()
+        .unwrap()
+        .bytes()
+        .unwrap();
+    assert_eq!(res, "first version");
+}
+
+#[test]
+fn test_s3_signer_server_side_encryption() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let mock_s3 = MockS3::setup(&rt);
+
+    // Write an object through a presigned URL that requires SSE-S3.
+    let s3_url = format!("s3://{}/my-encrypted-key", mock_s3.bucket());
+    let s3_signer = AwsFileSigner::new(mock_s3.client());
+    let options = SignOptions::new().with_server_side_encryption(ServerSideEncryption::Managed);
+    let presigned_url = rt
+        .block_on(s3_signer.sign_with_options(
+            &s3_url,
+            SystemTime::now(),
+            Duration::from_secs(3600),
+            Permission::Write,
+            &options,
+        ))
+        .unwrap();
+    assert_eq!(
+        presigned_url
+            .required_headers()
+            .get("x-amz-server-side-encryption")
+            .map(String::as_str),
+        Some("AES256")
+    );
+
+    let c = reqwest::blocking::Client::builder().build().unwrap();
+    let mut req = c.put(presigned_url.expose_url()).body("hello world");
+    for (name, value) in presigned_url.required_headers() {
+        req = req.header(name, value);
+    }
+    let res = req.send().unwrap().status();
+    assert_eq!(res, StatusCode::OK);
+    assert_eq!(
+        mock_s3
+            .server_side_encryption("my-encrypted-key")
+            .as_deref(),
+        Some("AES256")
+    );
+
+    // Omitting the required headers invalidates the signature.
+    let res = c
+        .put(presigned_url.expose_url())
+        .body("hello world")
+        .send()
+        .unwrap()
+        .status();
+    assert_eq!(res, StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn test_s3_signer_requester_pays() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let mock_s3 = MockS3::setup(&rt);
+
+    mock_s3.put_object("my-requester-pays-key");
+    let s3_url = format!("s3://{}/my-requester-pays-key", mock_s3.bucket());
+    let s3_signer = AwsFileSigner::new(mock_s3.client()).with_requester_pays(true);
+    let presigned_url = rt
+        .block_on(s3_signer.sign_read_only_starting_now(&s3_url, Duration::from_secs(3600)))
+        .unwrap();
+    assert_eq!(
+        presigned_url
+            .required_headers()
+            .get("x-amz-request-payer")
+            .map(String::as_str),
+        Some("requester")
+    );
+
+    let c = reqwest::blocking::Client::builder().build().unwrap();
+    let mut req = c.get(presigned_url.expose_url());
+    for (name, value) in presigned_url.required_headers() {
+        req = req.header(name, value);
+    }
+    let res = req.send().unwrap().bytes().unwrap();
+    assert_eq!(res, "hello world");
+}
+
+#[test]
+fn test_s3_signer_rejects_kms_for_reads() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let mock_s3 = MockS3::setup(&rt);
+
+    let s3_url = format!("s3://{}/my-key", mock_s3.bucket());
+    let s3_signer = AwsFileSigner::new(mock_s3.client());
+    let options =
+        SignOptions::new().with_server_side_encryption(ServerSideEncryption::Kms { key_id: None });
+    let err = rt
+        .block_on(s3_signer.sign_with_options(
+            &s3_url,
+            SystemTime::now(),
+            Duration::from_secs(3600),
+            Permission::Read,
+            &options,
+        ))
+        .unwrap_err();
+    assert_eq!(
+        err.kind(),
+        cloud_file_signer::SignerErrorKind::OptionNotSupported
+    );
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(16))]
+
+    #[test]
+    fn test_s3_signer_special_character_keys(key in "[^\\p{C}]{1,64}") {
+        prop_assume!(!key.split('/').any(|s| s == "." || s == ".."));
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let mock_s3 = MockS3::setup(&rt);
+        mock_s3.put_object(&key);
+
+        let s3_url = format!("s3://{}/{}", mock_s3.bucket(), key);
+        let s3_signer = AwsFileSigner::new(mock_s3.client());
+        let presigned_url = rt
+            .block_on(s3_signer.sign_read_only_starting_now(&s3_url, Duration::from_secs(3600)))
+            .unwrap();
+
+        let c = reqwest::blocking::Client::builder().build().unwrap();
+        let res = c.get(presigned_url.expose_url()).send().unwrap();
+        prop_assert_eq!(res.status(), StatusCode::OK);
+        prop_assert_eq!(res.bytes().unwrap(), "hello world");
+    }
+}